@@ -1,29 +1,79 @@
-use std::{cell::RefCell, process::Command, rc::Rc};
+use std::{
+    cell::RefCell,
+    cmp::Reverse,
+    os::unix::process::ExitStatusExt,
+    path::{Path, PathBuf},
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
 
-use freedesktop_desktop_entry::{DesktopEntry, get_languages_from_env};
+use freedesktop_desktop_entry::{
+    DesktopEntry, current_desktop, desktop_entries, get_languages_from_env,
+};
 // For some reason prelude::ListModelExtManual is making glib::clone! usable
 // although it it listed as an unused import. What?
 #[allow(unused_imports)]
 use gtk4::prelude::ListModelExtManual;
 use gtk4::{
-    ApplicationWindow, Box, Button, Entry, Label, Overlay, ScrolledWindow, Separator, Widget,
-    gio::{ActionEntry, SimpleActionGroup},
-    glib::{self, VariantTy, clone, object::IsA},
-    prelude::{BoxExt, ButtonExt, EditableExt, GtkWindowExt, WidgetExt},
+    ApplicationWindow, Box, Button, CheckButton, Entry, Label, Overlay, ScrolledWindow,
+    SearchEntry, Separator, TextBuffer, TextView, Widget,
+    gio::{self, ActionEntry, SimpleActionGroup},
+    glib::{self, VariantTy, clone, object::IsA, variant::ToVariant},
+    prelude::{
+        ApplicationExtManual, BoxExt, ButtonExt, CheckButtonExt, EditableExt, GtkWindowExt,
+        ObjectExt, TextBufferExt, TextViewExt, WidgetExt,
+    },
 };
-use libadwaita::{Easing, TimedAnimation, prelude::AnimationExt};
+use libadwaita::{Easing, TimedAnimation, Toast, ToastOverlay, prelude::AnimationExt};
 
-use crate::config::{
-    ShortcutNode, delete_shortcut_node, flatten_shortcuts_list, insert_shortcut_node,
-    load_shortcuts_from_config, save_shortcuts_json,
+use crate::{
+    cli::mode_query,
+    config::{
+        Settings, ShortcutNode, delete_shortcut_node, find_shortcut_node, flatten_shortcuts_list,
+        insert_shortcut_node, list_backups, load_shortcuts_from_config, notes_file_path,
+        parse_command_line, restore_backup, save_shortcuts_json, shortcuts_file_path,
+    },
+    history::{LaunchHistory, RunHistory},
+    icon_from_name,
+    launcher::{
+        CapturedOutput, CapturedRun, LaunchError, Launcher, RunMode, expand_exec, quote_command,
+        read_desktop_entry, run_captured,
+    },
+    searching::should_show_entry,
+    shortcuts::ShortcutsDisplay,
 };
 
+#[allow(clippy::too_many_arguments)]
 pub fn build_actions(
     window: &gtk4::ApplicationWindow,
     start_fade: &TimedAnimation,
     overlay: &Overlay,
+    toast_overlay: &ToastOverlay,
+    entry: &SearchEntry,
+    shortcuts_display: &Rc<ShortcutsDisplay>,
+    launch_history: &Arc<Mutex<LaunchHistory>>,
+    run_history: &Arc<Mutex<RunHistory>>,
+    launcher: &Rc<Launcher>,
+    settings: &Settings,
 ) -> Vec<ActionEntry<SimpleActionGroup>> {
+    let settings = settings.clone();
     vec![
+        ActionEntry::builder("show")
+            .activate(clone!(
+                #[weak]
+                window,
+                #[strong]
+                start_fade,
+                move |_, _, _| {
+                    window.set_sensitive(true);
+                    window.present();
+
+                    start_fade.set_reverse(false);
+                    start_fade.set_easing(Easing::EaseOutCirc);
+                    start_fade.play();
+                }
+            ))
+            .build(),
         ActionEntry::builder("close")
             .activate(clone!(
                 #[weak]
@@ -31,18 +81,106 @@ pub fn build_actions(
                 #[strong]
                 start_fade,
                 move |_, _, _| {
+                    // already closing
+                    if !window.is_sensitive() {
+                        return;
+                    }
                     // stop input
                     window.set_sensitive(false);
 
-                    // start animation
+                    // start animation, the window gets closed (or hidden) when it's done
                     start_fade.set_reverse(true);
                     start_fade.set_easing(Easing::EaseInCirc);
                     start_fade.play();
-
-                    // close when done
-                    start_fade.connect_done(move |_| {
-                        window.close();
-                    });
+                }
+            ))
+            .build(),
+        ActionEntry::builder("toggle")
+            .activate(clone!(
+                #[weak]
+                window,
+                move |_, _, _| {
+                    // not sensitive means it's already closing
+                    let action = if window.is_visible() && window.is_sensitive() {
+                        "wlshud.close"
+                    } else {
+                        "wlshud.show"
+                    };
+                    let _ =
+                        <ApplicationWindow as WidgetExt>::activate_action(&window, action, None);
+                }
+            ))
+            .build(),
+        ActionEntry::builder("set-query")
+            .parameter_type(Some(VariantTy::STRING))
+            .activate(clone!(
+                #[weak]
+                entry,
+                move |_, _, parameter| {
+                    if let Some(p) = parameter
+                        && let Some(query) = p.get::<String>()
+                    {
+                        entry.grab_focus();
+                        entry.set_text(&query);
+                        entry.set_position(-1);
+                    }
+                }
+            ))
+            .build(),
+        // (mode, search) from --mode, opens with the prefix for that mode in front
+        ActionEntry::builder("open-mode")
+            .parameter_type(Some(VariantTy::new("(ss)").unwrap()))
+            .activate(clone!(
+                #[weak]
+                entry,
+                #[weak]
+                toast_overlay,
+                move |_, _, parameter| {
+                    let Some((mode, search)) = parameter.and_then(|p| p.get::<(String, String)>())
+                    else {
+                        return;
+                    };
+                    match mode_query(&settings, &mode, &search) {
+                        Ok(query) => {
+                            entry.grab_focus();
+                            entry.set_text(&query);
+                            entry.set_position(-1);
+                        }
+                        Err(message) => show_error(&toast_overlay, &message),
+                    }
+                }
+            ))
+            .build(),
+        ActionEntry::builder("run-shortcut")
+            .parameter_type(Some(VariantTy::STRING))
+            .activate(clone!(
+                #[strong]
+                shortcuts_display,
+                move |_, _, parameter| {
+                    if let Some(p) = parameter
+                        && let Some(path) = p.get::<String>()
+                    {
+                        // same as typing out the path with the shortcuts focused
+                        shortcuts_display.reset();
+                        for c in path.chars() {
+                            shortcuts_display.handle_key_pressed(c);
+                        }
+                    }
+                }
+            ))
+            .build(),
+        ActionEntry::builder("reload-config")
+            .activate(clone!(
+                #[strong]
+                shortcuts_display,
+                #[weak]
+                toast_overlay,
+                move |_, _, _| {
+                    let (shortcuts, errors) = load_shortcuts_from_config();
+                    shortcuts_display.reload(&shortcuts);
+                    for error in errors {
+                        show_error(&toast_overlay, &error.to_string());
+                    }
                 }
             ))
             .build(),
@@ -51,27 +189,195 @@ pub fn build_actions(
             .activate(clone!(
                 #[weak]
                 window,
+                #[weak]
+                toast_overlay,
+                #[strong]
+                launch_history,
+                #[strong]
+                launcher,
                 move |_, _, parameter| {
                     if let Some(p) = parameter
                         && let Some(exec_list) = p.get::<Vec<String>>()
                         && !exec_list.is_empty()
                     {
-                        let mut exec = exec_list.iter();
-                        let mut cmd = Command::new(exec.next().unwrap());
-                        for arg in exec {
-                            cmd.arg(arg);
-                        }
-                        let _ = cmd.spawn();
-                        let _ = <ApplicationWindow as WidgetExt>::activate_action(
+                        let launching = clone!(
+                            #[strong]
+                            launcher,
+                            #[strong]
+                            exec_list,
+                            async move { launcher.launch(&exec_list).await }
+                        );
+                        finish_launch(
                             &window,
-                            "wlshud.close",
+                            &toast_overlay,
+                            &launch_history,
+                            launching,
+                            exec_list.clone(),
+                            exec_list,
                             None,
                         );
                     }
                 }
             ))
             .build(),
-        ActionEntry::builder("create-shortcut")
+        // same as exec, but errors offer to edit the shortcut at the path
+        ActionEntry::builder("exec-shortcut")
+            .parameter_type(Some(VariantTy::new("(sas)").unwrap()))
+            .activate(clone!(
+                #[weak]
+                window,
+                #[weak]
+                toast_overlay,
+                #[strong]
+                launch_history,
+                #[strong]
+                launcher,
+                move |_, _, parameter| {
+                    if let Some(p) = parameter
+                        && let Some((path, exec_list)) = p.get::<(String, Vec<String>)>()
+                        && !exec_list.is_empty()
+                    {
+                        let launching = clone!(
+                            #[strong]
+                            launcher,
+                            #[strong]
+                            exec_list,
+                            async move { launcher.launch(&exec_list).await }
+                        );
+                        finish_launch(
+                            &window,
+                            &toast_overlay,
+                            &launch_history,
+                            launching,
+                            exec_list.clone(),
+                            exec_list,
+                            Some(path),
+                        );
+                    }
+                }
+            ))
+            .build(),
+        // takes a desktop file path, a desktop action id (empty for the main entry) and files
+        // to open with it
+        ActionEntry::builder("launch")
+            .parameter_type(Some(VariantTy::new("(ssas)").unwrap()))
+            .activate(clone!(
+                #[weak]
+                window,
+                #[weak]
+                toast_overlay,
+                #[strong]
+                launch_history,
+                #[strong]
+                launcher,
+                move |_, _, parameter| {
+                    let Some((path, action, files)) =
+                        parameter.and_then(|p| p.get::<(String, String, Vec<String>)>())
+                    else {
+                        return;
+                    };
+                    let action = Some(action).filter(|a| !a.is_empty());
+                    let files = files.iter().map(PathBuf::from).collect::<Vec<PathBuf>>();
+                    let entry = read_desktop_entry(Path::new(&path)).ok();
+                    // recorded the same way search results are keyed, without any files
+                    let history_key = entry
+                        .as_ref()
+                        .and_then(|e| {
+                            let exec = match &action {
+                                Some(action) => e.action_exec(action),
+                                None => e.exec(),
+                            }?;
+                            expand_exec(exec, e, &[]).ok()
+                        })
+                        .unwrap_or_default();
+                    let command = entry
+                        .as_ref()
+                        .and_then(|e| {
+                            launcher
+                                .desktop_entry_argv(e, action.as_deref(), &files)
+                                .ok()
+                        })
+                        .unwrap_or_default();
+                    let launching = clone!(
+                        #[strong]
+                        launcher,
+                        async move {
+                            launcher
+                                .launch_desktop_entry(Path::new(&path), action.as_deref(), &files)
+                                .await
+                        }
+                    );
+                    finish_launch(
+                        &window,
+                        &toast_overlay,
+                        &launch_history,
+                        launching,
+                        history_key,
+                        command,
+                        None,
+                    );
+                }
+            ))
+            .build(),
+        // takes how to run it and the command line typed into the run prompt
+        ActionEntry::builder("run")
+            .parameter_type(Some(VariantTy::new("(ss)").unwrap()))
+            .activate(clone!(
+                #[weak]
+                window,
+                #[weak]
+                overlay,
+                #[weak]
+                toast_overlay,
+                #[strong]
+                launch_history,
+                #[strong]
+                run_history,
+                #[strong]
+                launcher,
+                move |_, _, parameter| {
+                    let Some((mode, command_line)) = parameter
+                        .and_then(|p| p.get::<(String, String)>())
+                        .and_then(|(mode, line)| Some((RunMode::parse(&mode)?, line)))
+                    else {
+                        return;
+                    };
+                    // remembered even if it fails, like a shell's history, so typos can be fixed
+                    run_history.lock().unwrap().record_run(mode, &command_line);
+                    let argv = launcher.run_argv(mode, &command_line);
+                    let command = argv.as_ref().cloned().unwrap_or_default();
+                    // these stay open to show the output instead of closing the HUD
+                    if mode == RunMode::Output {
+                        match argv.and_then(|argv| run_captured(&argv)) {
+                            Ok(run) => overlay.add_overlay(&build_output_overlay(
+                                &command_line,
+                                run,
+                                &overlay,
+                            )),
+                            Err(e) => show_launch_error(&toast_overlay, &e, &command, None),
+                        }
+                        return;
+                    }
+                    let launching = clone!(
+                        #[strong]
+                        launcher,
+                        async move { launcher.launch(&argv?).await }
+                    );
+                    // no history key, it's already in the run history and would only skew how
+                    // apps rank
+                    finish_launch(
+                        &window,
+                        &toast_overlay,
+                        &launch_history,
+                        launching,
+                        Vec::new(),
+                        command,
+                        None,
+                    );
+                }
+            ))
+            .build(),
+        ActionEntry::builder("open-with")
             .parameter_type(Some(VariantTy::STRING))
             .activate(clone!(
                 #[weak]
@@ -80,8 +386,43 @@ pub fn build_actions(
                     if let Some(p) = parameter
                         && let Some(file_path) = p.get::<String>()
                     {
+                        overlay.add_overlay(&build_open_with_overlay(file_path, &overlay));
+                    }
+                }
+            ))
+            .build(),
+        ActionEntry::builder("clear-history")
+            .activate(clone!(
+                #[strong]
+                launch_history,
+                #[strong]
+                run_history,
+                move |_, _, _| {
+                    launch_history.lock().unwrap().clear();
+                    run_history.lock().unwrap().clear();
+                }
+            ))
+            .build(),
+        // takes the file path and a desktop action id, empty for the main entry
+        ActionEntry::builder("create-shortcut")
+            .parameter_type(Some(VariantTy::new("(ss)").unwrap()))
+            .activate(clone!(
+                #[weak]
+                overlay,
+                #[strong]
+                launcher,
+                move |_, _, parameter| {
+                    if let Some(p) = parameter
+                        && let Some((file_path, desktop_action)) = p.get::<(String, String)>()
+                    {
+                        let desktop_action = Some(desktop_action).filter(|a| !a.is_empty());
                         // Builds the overlay to show
-                        overlay.add_overlay(&build_create_shortcut_overlay(file_path, &overlay));
+                        overlay.add_overlay(&build_create_shortcut_overlay(
+                            file_path,
+                            desktop_action,
+                            &overlay,
+                            &launcher,
+                        ));
                     }
                 }
             ))
@@ -92,7 +433,73 @@ pub fn build_actions(
                 #[weak]
                 overlay,
                 move |_, _, _| {
-                    overlay.add_overlay(&build_add_command_shortcut_overlay(&overlay));
+                    overlay.add_overlay(&build_command_shortcut_overlay(&overlay, None));
+                }
+            ))
+            .build(),
+        // takes the path of characters of the shortcut to edit
+        ActionEntry::builder("edit-shortcut")
+            .parameter_type(Some(VariantTy::STRING))
+            .activate(clone!(
+                #[weak]
+                overlay,
+                move |_, _, parameter| {
+                    if let Some(p) = parameter
+                        && let Some(path) = p.get::<String>()
+                    {
+                        overlay.add_overlay(&build_command_shortcut_overlay(&overlay, Some(path)));
+                    }
+                }
+            ))
+            .build(),
+        ActionEntry::builder("restore-backups")
+            .parameter_type(None)
+            .activate(clone!(
+                #[weak]
+                overlay,
+                move |_, _, _| {
+                    overlay.add_overlay(&build_restore_backups_overlay(&overlay));
+                }
+            ))
+            .build(),
+        ActionEntry::builder("show-error")
+            .parameter_type(Some(VariantTy::STRING))
+            .activate(clone!(
+                #[weak]
+                toast_overlay,
+                move |_, _, parameter| {
+                    if let Some(p) = parameter
+                        && let Some(message) = p.get::<String>()
+                    {
+                        // toast titles are markup, error messages may contain < or &
+                        let toast = Toast::builder()
+                            .title(glib::markup_escape_text(&message))
+                            // stays until dismissed so it can actually be read
+                            .timeout(0)
+                            .build();
+                        toast_overlay.add_toast(toast);
+                    }
+                }
+            ))
+            .build(),
+        ActionEntry::builder("copy")
+            .parameter_type(Some(VariantTy::STRING))
+            .activate(clone!(
+                #[weak]
+                window,
+                #[weak]
+                toast_overlay,
+                move |_, _, parameter| {
+                    if let Some(p) = parameter
+                        && let Some(text) = p.get::<String>()
+                    {
+                        copy_to_clipboard(&window, &text);
+                        let toast = Toast::builder()
+                            .title(glib::markup_escape_text(&format!("Copied {}", text)))
+                            .timeout(2)
+                            .build();
+                        toast_overlay.add_toast(toast);
+                    }
                 }
             ))
             .build(),
@@ -109,6 +516,139 @@ pub fn build_actions(
     ]
 }
 
+// Shows an error toast from anywhere inside the window
+pub fn show_error(widget: &impl IsA<Widget>, message: &str) {
+    let _ = widget.activate_action("wlshud.show-error", Some(&message.to_variant()));
+}
+
+// On Wayland whatever's copied is served by the program that copied it, so it'd be gone once
+// the HUD closes and wlshud exits (without daemon mode). This keeps wlshud running in the
+// background until something else takes over the clipboard.
+pub fn copy_to_clipboard(widget: &impl IsA<Widget>, text: &str) {
+    let clipboard = widget.clipboard();
+    clipboard.set_text(text);
+    let Some(app) = gio::Application::default() else {
+        return;
+    };
+    let hold = RefCell::new(Some(app.hold()));
+    let handler = Rc::new(RefCell::new(None));
+    let id = clipboard.connect_local_notify(clone!(
+        #[strong]
+        handler,
+        move |clipboard| {
+            if clipboard.is_local() {
+                return;
+            }
+            drop(hold.take());
+            if let Some(id) = handler.take() {
+                clipboard.disconnect(id);
+            }
+        }
+    ));
+    handler.replace(Some(id));
+}
+
+// Waits for the launch to get past the launcher's check time without blocking, then records it
+// and closes the HUD. If it failed the HUD stays open with a toast saying why instead.
+fn finish_launch(
+    window: &ApplicationWindow,
+    toast_overlay: &ToastOverlay,
+    launch_history: &Arc<Mutex<LaunchHistory>>,
+    launching: impl Future<Output = Result<(), LaunchError>> + 'static,
+    history_key: Vec<String>,
+    command: Vec<String>,
+    shortcut_path: Option<String>,
+) {
+    // no input while it's pending, so a double Enter or click can't launch it twice. Going
+    // insensitive drops the focus, so remember where it was for when the launch fails.
+    let focused = GtkWindowExt::focus(window);
+    window.set_sensitive(false);
+    glib::spawn_future_local(clone!(
+        #[weak]
+        window,
+        #[weak]
+        toast_overlay,
+        #[strong]
+        launch_history,
+        async move {
+            let result = launching.await;
+            // close treats an insensitive window as already closing
+            window.set_sensitive(true);
+            match result {
+                Ok(()) => {
+                    if !history_key.is_empty() {
+                        launch_history.lock().unwrap().record_launch(&history_key);
+                    }
+                    let _ = <ApplicationWindow as WidgetExt>::activate_action(
+                        &window,
+                        "wlshud.close",
+                        None,
+                    );
+                }
+                Err(e) => {
+                    if let Some(focused) = focused {
+                        focused.grab_focus();
+                    }
+                    show_launch_error(&toast_overlay, &e, &command, shortcut_path);
+                }
+            }
+        }
+    ));
+}
+
+fn show_launch_error(
+    toast_overlay: &ToastOverlay,
+    error: &LaunchError,
+    command: &[String],
+    shortcut_path: Option<String>,
+) {
+    // stays until dismissed like other errors
+    let toast = Toast::builder().timeout(0).build();
+    let title_box = Box::builder()
+        .orientation(gtk4::Orientation::Horizontal)
+        .spacing(8)
+        .build();
+    title_box.append(
+        &Label::builder()
+            .label(error.to_string())
+            .wrap(true)
+            .hexpand(true)
+            .build(),
+    );
+
+    if !command.is_empty() {
+        let copy_button = Button::builder().label("Copy command").build();
+        let quoted = quote_command(command);
+        copy_button.connect_clicked(clone!(
+            #[weak]
+            toast,
+            move |button| {
+                copy_to_clipboard(button, &quoted);
+                toast.dismiss();
+            }
+        ));
+        title_box.append(&copy_button);
+    }
+    if let Some(path) = shortcut_path {
+        let edit_button = Button::builder()
+            .label("Edit shortcut")
+            .action_name("wlshud.edit-shortcut")
+            .action_target(&path.to_variant())
+            .build();
+        edit_button.connect_clicked(clone!(
+            #[weak]
+            toast,
+            move |_| {
+                toast.dismiss();
+            }
+        ));
+        title_box.append(&edit_button);
+    }
+
+    toast.set_custom_title(Some(&title_box));
+    toast_overlay.add_toast(toast);
+}
+
 fn build_overlay_base() -> gtk4::CenterBox {
     gtk4::CenterBox::builder()
         .hexpand(true)
@@ -117,7 +657,12 @@ fn build_overlay_base() -> gtk4::CenterBox {
         .build()
 }
 
-fn build_create_shortcut_overlay(file_path: String, overlay: &Overlay) -> impl IsA<Widget> {
+fn build_create_shortcut_overlay(
+    file_path: String,
+    desktop_action: Option<String>,
+    overlay: &Overlay,
+    launcher: &Rc<Launcher>,
+) -> impl IsA<Widget> {
     let base = build_overlay_base();
     let center_box = gtk4::Box::builder()
         .halign(gtk4::Align::Center)
@@ -130,7 +675,10 @@ fn build_create_shortcut_overlay(file_path: String, overlay: &Overlay) -> impl I
     base.set_center_widget(Some(&center_box));
 
     let title = Label::builder()
-        .label(format!("Adding shortcut for {}", &file_path))
+        .label(match &desktop_action {
+            Some(action) => format!("Adding shortcut for {} ({})", &file_path, action),
+            None => format!("Adding shortcut for {}", &file_path),
+        })
         .css_classes(["title"])
         .build();
     let path_entry_label = Label::builder()
@@ -150,6 +698,8 @@ fn build_create_shortcut_overlay(file_path: String, overlay: &Overlay) -> impl I
         base,
         #[weak]
         overlay,
+        #[strong]
+        launcher,
         move |_| {
             let mut character_path = path_entry.text().trim().to_owned();
             if character_path.is_empty() {
@@ -163,7 +713,11 @@ fn build_create_shortcut_overlay(file_path: String, overlay: &Overlay) -> impl I
                 exec: Some(
                     parsed_desktop_entry
                         .as_ref()
-                        .and_then(|e| e.parse_exec().ok())
+                        .and_then(|e| {
+                            launcher
+                                .desktop_entry_argv(e, desktop_action.as_deref(), &[])
+                                .ok()
+                        })
                         // Defaults to opening a file if the file is not a valid .desktop file
                         .unwrap_or(vec!["xdg-open".to_owned(), file_path.clone()]),
                 ),
@@ -173,14 +727,17 @@ fn build_create_shortcut_overlay(file_path: String, overlay: &Overlay) -> impl I
                         .and_then(|e| e.icon().map(|s| s.to_owned()))
                         .unwrap_or("external-link-symbolic".to_owned()),
                 ),
+                shell: false,
             };
 
-            let mut shortcuts: Vec<ShortcutNode> = load_shortcuts_from_config();
+            let (mut shortcuts, _) = load_shortcuts_from_config();
 
             insert_shortcut_node(&mut character_path.chars(), to_insert, &mut shortcuts);
 
             // save new shortcuts
-            save_shortcuts_json(&shortcuts);
+            if let Err(e) = save_shortcuts_json(&shortcuts) {
+                show_error(&overlay, &e.to_string());
+            }
 
             // close overlay
             overlay.remove_overlay(&base);
@@ -220,7 +777,7 @@ fn build_remove_shortcuts_overlay(overlay: &Overlay) -> impl IsA<Widget> {
         .css_classes(["suggested-action"])
         .build();
 
-    let shortcuts_original: Vec<ShortcutNode> = load_shortcuts_from_config();
+    let (shortcuts_original, _) = load_shortcuts_from_config();
     let shortcuts = flatten_shortcuts_list(&shortcuts_original, "");
 
     center_box.append(&title);
@@ -296,7 +853,9 @@ fn build_remove_shortcuts_overlay(overlay: &Overlay) -> impl IsA<Widget> {
             }
 
             // save new shortcuts
-            save_shortcuts_json(&shortcuts);
+            if let Err(e) = save_shortcuts_json(&shortcuts) {
+                show_error(&overlay, &e.to_string());
+            }
 
             // close overlay
             overlay.remove_overlay(&base);
@@ -308,7 +867,11 @@ fn build_remove_shortcuts_overlay(overlay: &Overlay) -> impl IsA<Widget> {
     base
 }
 
-fn build_add_command_shortcut_overlay(overlay: &Overlay) -> impl IsA<Widget> {
+// Adds a new command shortcut, or edits the one at `editing_path`
+fn build_command_shortcut_overlay(
+    overlay: &Overlay,
+    editing_path: Option<String>,
+) -> impl IsA<Widget> {
     let base = build_overlay_base();
     let center_box = gtk4::Box::builder()
         .halign(gtk4::Align::Center)
@@ -321,7 +884,10 @@ fn build_add_command_shortcut_overlay(overlay: &Overlay) -> impl IsA<Widget> {
     base.set_center_widget(Some(&center_box));
 
     let title = Label::builder()
-        .label("Add a new shortcut to trigger a command")
+        .label(match &editing_path {
+            Some(path) => format!("Edit the shortcut {}", path),
+            None => "Add a new shortcut to trigger a command".to_owned(),
+        })
         .css_classes(["title"])
         .build();
     let path_entry_label = Label::builder()
@@ -339,10 +905,35 @@ fn build_add_command_shortcut_overlay(overlay: &Overlay) -> impl IsA<Widget> {
         .halign(gtk4::Align::Start)
         .build();
     let cmd_entry = Entry::builder().build();
+    let shell_check = CheckButton::builder()
+        .label("Run with sh -c, for pipes, redirects and other shell syntax")
+        .build();
     let finish_button = Button::builder()
-        .label("Add Shortcut")
+        .label(if editing_path.is_some() {
+            "Save Shortcut"
+        } else {
+            "Add Shortcut"
+        })
         .css_classes(["suggested-action"])
         .build();
+
+    if let Some(path) = &editing_path {
+        let (mut shortcuts, _) = load_shortcuts_from_config();
+        if let Some(node) = find_shortcut_node(&mut path.chars(), &mut shortcuts) {
+            icon_entry.set_text(node.icon.as_deref().unwrap_or_default());
+            let command_line = match &node.exec {
+                Some(exec) if node.shell => exec.join(" "),
+                // quoted so it parses back into the same arguments
+                Some(exec) => quote_command(exec),
+                None => String::new(),
+            };
+            cmd_entry.set_text(&command_line);
+            shell_check.set_active(node.shell);
+        }
+        path_entry.set_text(path);
+        // only the command and icon can change
+        path_entry.set_sensitive(false);
+    }
     let cancel_button = Button::builder().label("Cancel").build();
     let buttons_row = Box::builder()
         .orientation(gtk4::Orientation::Horizontal)
@@ -360,6 +951,8 @@ fn build_add_command_shortcut_overlay(overlay: &Overlay) -> impl IsA<Widget> {
         #[weak]
         icon_entry,
         #[weak]
+        shell_check,
+        #[weak]
         base,
         #[weak]
         overlay,
@@ -368,24 +961,50 @@ fn build_add_command_shortcut_overlay(overlay: &Overlay) -> impl IsA<Widget> {
             if character_path.is_empty() {
                 return;
             }
-            let last_char = character_path.pop().unwrap();
-            let to_insert = ShortcutNode {
-                character: last_char,
-                exec: Some(cmd_entry.text().split(' ').map(|s| s.to_owned()).collect()),
-                children: Vec::new(),
-                icon: if icon_entry.text().is_empty() {
-                    Some("terminal-symbolic".to_owned())
-                } else {
-                    Some(icon_entry.text().to_string())
-                },
+            let shell = shell_check.is_active();
+            let exec = if shell {
+                Some(vec![cmd_entry.text().to_string()])
+            } else {
+                match parse_command_line(&cmd_entry.text()) {
+                    Ok(exec) => Some(exec),
+                    Err(e) => {
+                        show_error(&overlay, &format!("Could not parse the command: {}", e));
+                        return;
+                    }
+                }
+            };
+            let icon = if icon_entry.text().is_empty() {
+                Some("terminal-symbolic".to_owned())
+            } else {
+                Some(icon_entry.text().to_string())
             };
 
-            let mut shortcuts: Vec<ShortcutNode> = load_shortcuts_from_config();
+            let (mut shortcuts, _) = load_shortcuts_from_config();
 
-            insert_shortcut_node(&mut character_path.chars(), to_insert, &mut shortcuts);
+            if editing_path.is_some() {
+                // keeps its children
+                if let Some(node) = find_shortcut_node(&mut character_path.chars(), &mut shortcuts)
+                {
+                    node.exec = exec;
+                    node.icon = icon;
+                    node.shell = shell;
+                }
+            } else {
+                let last_char = character_path.pop().unwrap();
+                let to_insert = ShortcutNode {
+                    character: last_char,
+                    exec,
+                    children: Vec::new(),
+                    icon,
+                    shell,
+                };
+                insert_shortcut_node(&mut character_path.chars(), to_insert, &mut shortcuts);
+            }
 
             // save new shortcuts
-            save_shortcuts_json(&shortcuts);
+            if let Err(e) = save_shortcuts_json(&shortcuts) {
+                show_error(&overlay, &e.to_string());
+            }
 
             // close overlay
             overlay.remove_overlay(&base);
@@ -410,7 +1029,351 @@ fn build_add_command_shortcut_overlay(overlay: &Overlay) -> impl IsA<Widget> {
     center_box.append(&icon_entry);
     center_box.append(&cmd_entry_label);
     center_box.append(&cmd_entry);
+    center_box.append(&shell_check);
     center_box.append(&buttons_row);
 
     base
 }
+
+fn build_restore_backups_overlay(overlay: &Overlay) -> impl IsA<Widget> {
+    let base = build_overlay_base();
+    let center_box = gtk4::Box::builder()
+        .halign(gtk4::Align::Center)
+        .valign(gtk4::Align::Center)
+        .orientation(gtk4::Orientation::Vertical)
+        .css_classes(["dialog-box"])
+        .spacing(8)
+        .opacity(1.0)
+        .build();
+    base.set_center_widget(Some(&center_box));
+
+    let title = Label::builder()
+        .label("Restore a backup")
+        .css_classes(["title"])
+        .build();
+    let hint_label = Label::builder()
+        .label("The current version is backed up before restoring. wlshud closes afterwards to reload.")
+        .halign(gtk4::Align::Start)
+        .build();
+    let cancel_button = Button::builder().label("Cancel").build();
+
+    center_box.append(&title);
+    center_box.append(&Separator::new(gtk4::Orientation::Horizontal));
+    center_box.append(&hint_label);
+    let scroll_box = Box::builder()
+        .orientation(gtk4::Orientation::Vertical)
+        .spacing(8)
+        .build();
+    let scroll_window = ScrolledWindow::builder()
+        .child(&scroll_box)
+        .hscrollbar_policy(gtk4::PolicyType::Never)
+        .height_request(600)
+        .build();
+
+    let mut backups = list_backups(&shortcuts_file_path());
+    backups.append(&mut list_backups(&notes_file_path()));
+    backups.sort_by_key(|b| Reverse(b.time));
+    if backups.is_empty() {
+        scroll_box.append(&Label::new(Some("No backups yet")));
+    }
+    for backup in backups {
+        let row = Box::builder()
+            .spacing(16)
+            .orientation(gtk4::Orientation::Horizontal)
+            .build();
+        let secs = backup
+            .time
+            .duration_since(std::time::UNIX_EPOCH)
+            .map(|d| d.as_secs() as i64)
+            .unwrap_or(0);
+        let time_str = glib::DateTime::from_unix_local(secs)
+            .and_then(|t| t.format("%F %T"))
+            .map(|s| s.to_string())
+            .unwrap_or(secs.to_string());
+        let label = Label::builder()
+            .label(format!(
+                "{} from {}",
+                backup
+                    .original
+                    .file_name()
+                    .unwrap_or_default()
+                    .to_string_lossy(),
+                time_str
+            ))
+            .hexpand(true)
+            .halign(gtk4::Align::Start)
+            .build();
+        let restore_button = Button::builder().label("Restore").build();
+        restore_button.connect_clicked(clone!(
+            #[weak]
+            overlay,
+            move |_| {
+                if let Err(e) = restore_backup(&backup) {
+                    show_error(&overlay, &format!("Could not restore backup: {}", e));
+                } else {
+                    let _ = overlay.activate_action("wlshud.close", None);
+                }
+            }
+        ));
+        row.append(&label);
+        row.append(&restore_button);
+
+        scroll_box.append(&row);
+    }
+
+    cancel_button.connect_clicked(clone!(
+        #[weak]
+        base,
+        #[weak]
+        overlay,
+        move |_| {
+            // close overlay
+            overlay.remove_overlay(&base);
+        }
+    ));
+
+    center_box.append(&scroll_window);
+    center_box.append(&Separator::new(gtk4::Orientation::Horizontal));
+    center_box.append(&cancel_button);
+
+    base
+}
+
+// past this much output it's probably something like `yes`, stop showing it so the HUD keeps up
+const MAX_CAPTURED_OUTPUT: usize = 1024 * 1024;
+
+// Shows the output of a >> command as it comes in, with how it exited once it's done
+fn build_output_overlay(
+    command_line: &str,
+    run: CapturedRun,
+    overlay: &Overlay,
+) -> impl IsA<Widget> {
+    let base = build_overlay_base();
+    let center_box = gtk4::Box::builder()
+        .halign(gtk4::Align::Center)
+        .valign(gtk4::Align::Center)
+        .orientation(gtk4::Orientation::Vertical)
+        .css_classes(["dialog-box"])
+        .spacing(8)
+        .opacity(1.0)
+        .build();
+    base.set_center_widget(Some(&center_box));
+
+    let title = Label::builder()
+        .label(command_line)
+        .css_classes(["title"])
+        .wrap(true)
+        .build();
+    let buffer = TextBuffer::new(None);
+    // keeps the view scrolled to the newest output
+    let end_mark = buffer.create_mark(None, &buffer.end_iter(), false);
+    let text_view = TextView::builder()
+        .buffer(&buffer)
+        .editable(false)
+        .cursor_visible(false)
+        .monospace(true)
+        .wrap_mode(gtk4::WrapMode::WordChar)
+        .build();
+    let scroll_window = ScrolledWindow::builder()
+        .child(&text_view)
+        .hscrollbar_policy(gtk4::PolicyType::Never)
+        .height_request(600)
+        .width_request(800)
+        .build();
+    let status_label = Label::builder()
+        .label("Running…")
+        .css_classes(["subtitle"])
+        .halign(gtk4::Align::Start)
+        .build();
+    let buttons_box = Box::builder()
+        .orientation(gtk4::Orientation::Horizontal)
+        .spacing(8)
+        .halign(gtk4::Align::End)
+        .build();
+    let copy_button = Button::builder().label("Copy output").build();
+    let close_button = Button::builder().label("Close").build();
+
+    let run = Rc::new(run);
+    glib::spawn_future_local(clone!(
+        #[weak]
+        buffer,
+        #[weak]
+        text_view,
+        #[weak]
+        status_label,
+        #[strong]
+        run,
+        async move {
+            let mut shown = 0;
+            while let Ok(message) = run.output.recv().await {
+                match message {
+                    CapturedOutput::Text(text) => {
+                        if shown > MAX_CAPTURED_OUTPUT {
+                            continue;
+                        }
+                        shown += text.len();
+                        buffer.insert(&mut buffer.end_iter(), &text);
+                        if shown > MAX_CAPTURED_OUTPUT {
+                            buffer.insert(&mut buffer.end_iter(), "\n[output cut off here]\n");
+                        }
+                        text_view.scroll_mark_onscreen(&end_mark);
+                    }
+                    CapturedOutput::Exited(status) => {
+                        let (message, succeeded) = match status {
+                            Ok(status) => match (status.code(), status.signal()) {
+                                (Some(0), _) => ("Finished".to_owned(), true),
+                                (Some(code), _) => (format!("Exited with status {}", code), false),
+                                (None, Some(signal)) => {
+                                    (format!("Stopped by signal {}", signal), false)
+                                }
+                                (None, None) => ("Stopped".to_owned(), false),
+                            },
+                            Err(e) => (format!("Could not wait for it to finish: {}", e), false),
+                        };
+                        status_label.set_text(&message);
+                        status_label.add_css_class(if succeeded { "success" } else { "error" });
+                    }
+                }
+            }
+        }
+    ));
+
+    copy_button.connect_clicked(clone!(
+        #[weak]
+        buffer,
+        move |button| {
+            let (start, end) = buffer.bounds();
+            copy_to_clipboard(button, &buffer.text(&start, &end, false));
+        }
+    ));
+    close_button.connect_clicked(clone!(
+        #[weak]
+        base,
+        #[weak]
+        overlay,
+        move |_| {
+            overlay.remove_overlay(&base);
+        }
+    ));
+    // don't leave it running once nobody can see it, e.g. when the HUD closes
+    base.connect_unmap(clone!(
+        #[strong]
+        run,
+        move |_| {
+            run.stop();
+        }
+    ));
+
+    center_box.append(&title);
+    center_box.append(&Separator::new(gtk4::Orientation::Horizontal));
+    center_box.append(&scroll_window);
+    center_box.append(&status_label);
+    buttons_box.append(&copy_button);
+    buttons_box.append(&close_button);
+    center_box.append(&buttons_box);
+
+    base
+}
+
+// Lists the apps that say they can open the file's type, picking one launches it with the file
+fn build_open_with_overlay(file_path: String, overlay: &Overlay) -> impl IsA<Widget> {
+    let base = build_overlay_base();
+    let center_box = gtk4::Box::builder()
+        .halign(gtk4::Align::Center)
+        .valign(gtk4::Align::Center)
+        .orientation(gtk4::Orientation::Vertical)
+        .css_classes(["dialog-box"])
+        .spacing(8)
+        .opacity(1.0)
+        .build();
+    base.set_center_widget(Some(&center_box));
+
+    let (content_type, _) = gio::content_type_guess(Some(&file_path), None);
+    let mime_type = gio::content_type_get_mime_type(&content_type)
+        .map(|m| m.to_string())
+        .unwrap_or(content_type.to_string());
+
+    let title = Label::builder()
+        .label(format!("Open {} with", &file_path))
+        .css_classes(["title"])
+        .build();
+    let cancel_button = Button::builder().label("Cancel").build();
+
+    center_box.append(&title);
+    center_box.append(&Separator::new(gtk4::Orientation::Horizontal));
+    let scroll_box = Box::builder()
+        .orientation(gtk4::Orientation::Vertical)
+        .spacing(8)
+        .build();
+    let scroll_window = ScrolledWindow::builder()
+        .child(&scroll_box)
+        .hscrollbar_policy(gtk4::PolicyType::Never)
+        .height_request(600)
+        .build();
+
+    let locales = get_languages_from_env();
+    let desktops = current_desktop().unwrap_or_default();
+    let mut apps = desktop_entries(&locales)
+        .into_iter()
+        .filter(|e| {
+            should_show_entry(e, &desktops)
+                && e.mime_type()
+                    .is_some_and(|types| types.contains(&mime_type.as_str()))
+        })
+        .map(|e| {
+            let name = e
+                .name(&locales)
+                .map(|n| n.to_string())
+                .unwrap_or(e.appid.clone());
+            (name, e)
+        })
+        .collect::<Vec<(String, DesktopEntry)>>();
+    apps.sort_by(|a, b| a.0.cmp(&b.0));
+    apps.dedup_by(|a, b| a.1.appid == b.1.appid);
+    if apps.is_empty() {
+        scroll_box.append(&Label::new(Some(&format!(
+            "No apps found that open {}",
+            mime_type
+        ))));
+    }
+    for (name, app) in apps {
+        let row = Box::builder()
+            .spacing(16)
+            .orientation(gtk4::Orientation::Horizontal)
+            .build();
+        let icon = icon_from_name(app.icon().unwrap_or("external-link-symbolic"));
+        icon.set_icon_size(gtk4::IconSize::Large);
+        let open_button = Button::builder()
+            .label(name)
+            .hexpand(true)
+            .action_name("wlshud.launch")
+            .action_target(
+                &(
+                    app.path.to_string_lossy().to_string(),
+                    String::new(),
+                    vec![file_path.clone()],
+                )
+                    .to_variant(),
+            )
+            .build();
+        row.append(&icon);
+        row.append(&open_button);
+
+        scroll_box.append(&row);
+    }
+
+    cancel_button.connect_clicked(clone!(
+        #[weak]
+        base,
+        #[weak]
+        overlay,
+        move |_| {
+            overlay.remove_overlay(&base);
+        }
+    ));
+
+    center_box.append(&scroll_window);
+    center_box.append(&cancel_button);
+
+    base
+}
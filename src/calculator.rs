@@ -0,0 +1,426 @@
+// Evaluates arithmetic typed into the search bar, like 2*(3+4)/7, sqrt(2) or 0xff + 0b101
+
+pub struct Calculation {
+    pub value: f64,
+    // whether it did anything beyond reading a number or constant, a bare 5 or pi isn't worth
+    // a result row unless it was asked for
+    pub computed: bool,
+}
+
+// how deep parentheses, signs and powers can nest, far more than anyone types but few enough that
+// pasting a wall of ((((( can't run the search thread out of stack
+const MAX_NESTING: usize = 256;
+
+#[derive(Clone, PartialEq)]
+enum Token {
+    // the bool is true for 0x, 0b and 0o literals
+    Number(f64, bool),
+    Name(String),
+    Operator(char),
+    OpenParen,
+    CloseParen,
+    Comma,
+}
+
+// None if it isn't a valid expression or the answer isn't a finite number (like 1/0)
+pub fn evaluate(expression: &str) -> Option<Calculation> {
+    let tokens = tokenize(expression)?;
+    if tokens.is_empty() {
+        return None;
+    }
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        depth: 0,
+        computed: false,
+    };
+    let value = parser.expression()?;
+    if parser.pos != parser.tokens.len() || !value.is_finite() {
+        return None;
+    }
+    Some(Calculation {
+        value,
+        computed: parser.computed,
+    })
+}
+
+// Whole numbers as integers, everything else rounded to 10 significant-ish digits
+pub fn format_number(value: f64) -> String {
+    let trim_zeros = |s: &str| {
+        if s.contains('.') {
+            s.trim_end_matches('0').trim_end_matches('.').to_owned()
+        } else {
+            s.to_owned()
+        }
+    };
+    let magnitude = value.abs();
+    if value.fract() == 0.0 && magnitude < 1e15 {
+        format!("{}", value as i64)
+    } else if !(1e-6..1e15).contains(&magnitude) {
+        let formatted = format!("{:.9e}", value);
+        match formatted.split_once('e') {
+            Some((mantissa, exponent)) => format!("{}e{}", trim_zeros(mantissa), exponent),
+            None => formatted,
+        }
+    } else {
+        trim_zeros(&format!("{:.10}", value))
+    }
+}
+
+fn tokenize(expression: &str) -> Option<Vec<Token>> {
+    let chars = expression.chars().collect::<Vec<char>>();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+        } else if c.is_ascii_digit() || c == '.' {
+            let (token, len) = read_number(&chars[i..])?;
+            tokens.push(token);
+            i += len;
+        } else if c.is_alphabetic() || c == '_' {
+            let len = chars[i..]
+                .iter()
+                .take_while(|c| c.is_alphanumeric() || **c == '_')
+                .count();
+            let name = chars[i..i + len].iter().collect::<String>();
+            tokens.push(Token::Name(name.to_lowercase()));
+            i += len;
+        } else {
+            tokens.push(match c {
+                '(' => Token::OpenParen,
+                ')' => Token::CloseParen,
+                ',' => Token::Comma,
+                // ** is the other way of writing powers
+                '*' if chars.get(i + 1) == Some(&'*') => {
+                    i += 1;
+                    Token::Operator('^')
+                }
+                '+' | '-' | '*' | '/' | '%' | '^' => Token::Operator(c),
+                '×' => Token::Operator('*'),
+                '÷' => Token::Operator('/'),
+                '−' => Token::Operator('-'),
+                _ => return None,
+            });
+            i += 1;
+        }
+    }
+    Some(tokens)
+}
+
+// A number at the start of `chars` and how many chars it took up
+fn read_number(chars: &[char]) -> Option<(Token, usize)> {
+    let radix = match chars {
+        ['0', 'x' | 'X', ..] => Some(16),
+        ['0', 'b' | 'B', ..] => Some(2),
+        ['0', 'o' | 'O', ..] => Some(8),
+        _ => None,
+    };
+    if let Some(radix) = radix {
+        let len = chars[2..]
+            .iter()
+            .take_while(|c| c.is_digit(radix) || **c == '_')
+            .count();
+        let digits = chars[2..2 + len]
+            .iter()
+            .filter(|c| **c != '_')
+            .collect::<String>();
+        let value = u64::from_str_radix(&digits, radix).ok()?;
+        return Some((Token::Number(value as f64, true), 2 + len));
+    }
+
+    let mut len = chars
+        .iter()
+        .take_while(|c| c.is_ascii_digit() || **c == '.')
+        .count();
+    // an exponent, only if digits follow so 2e doesn't eat the e
+    if let Some('e' | 'E') = chars.get(len) {
+        let sign = matches!(chars.get(len + 1), Some('+' | '-')) as usize;
+        let digits = chars[(len + 1 + sign).min(chars.len())..]
+            .iter()
+            .take_while(|c| c.is_ascii_digit())
+            .count();
+        if digits > 0 {
+            len += 1 + sign + digits;
+        }
+    }
+    let value = chars[..len].iter().collect::<String>().parse().ok()?;
+    Some((Token::Number(value, false), len))
+}
+
+// Recursive descent, lowest precedence first: + -, then * / %, then unary minus, then ^ (which
+// groups to the right, so 2^3^2 is 2^9 and -2^2 is -4)
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    // how many unary() calls are on the stack, every kind of nesting goes through it
+    depth: usize,
+    computed: bool,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn eat(&mut self, token: &Token) -> bool {
+        if self.peek() == Some(token) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expression(&mut self) -> Option<f64> {
+        let mut value = self.term()?;
+        while let Some(Token::Operator(op @ ('+' | '-'))) = self.peek().cloned() {
+            self.pos += 1;
+            let rhs = self.term()?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+            self.computed = true;
+        }
+        Some(value)
+    }
+
+    fn term(&mut self) -> Option<f64> {
+        let mut value = self.unary()?;
+        while let Some(Token::Operator(op @ ('*' | '/' | '%'))) = self.peek().cloned() {
+            self.pos += 1;
+            let rhs = self.unary()?;
+            value = match op {
+                '*' => value * rhs,
+                '/' => value / rhs,
+                _ => value % rhs,
+            };
+            self.computed = true;
+        }
+        Some(value)
+    }
+
+    fn unary(&mut self) -> Option<f64> {
+        if self.depth >= MAX_NESTING {
+            return None;
+        }
+        self.depth += 1;
+        let value = self.signed();
+        self.depth -= 1;
+        value
+    }
+
+    fn signed(&mut self) -> Option<f64> {
+        if self.eat(&Token::Operator('-')) {
+            return Some(-self.unary()?);
+        }
+        if self.eat(&Token::Operator('+')) {
+            return self.unary();
+        }
+        self.power()
+    }
+
+    fn power(&mut self) -> Option<f64> {
+        let base = self.primary()?;
+        if self.eat(&Token::Operator('^')) {
+            // the exponent can have its own minus, as in 2^-1
+            let exponent = self.unary()?;
+            self.computed = true;
+            return Some(base.powf(exponent));
+        }
+        Some(base)
+    }
+
+    fn primary(&mut self) -> Option<f64> {
+        match self.next()? {
+            Token::Number(value, other_base) => {
+                if other_base {
+                    self.computed = true;
+                }
+                Some(value)
+            }
+            Token::OpenParen => {
+                let value = self.expression()?;
+                self.eat(&Token::CloseParen).then_some(value)
+            }
+            Token::Name(name) => {
+                if self.eat(&Token::OpenParen) {
+                    let args = self.arguments()?;
+                    self.computed = true;
+                    call_function(&name, &args)
+                } else {
+                    constant(&name)
+                }
+            }
+            _ => None,
+        }
+    }
+
+    // what's between a function's parentheses, after the opening one
+    fn arguments(&mut self) -> Option<Vec<f64>> {
+        let mut args = Vec::new();
+        if self.eat(&Token::CloseParen) {
+            return Some(args);
+        }
+        loop {
+            args.push(self.expression()?);
+            if self.eat(&Token::CloseParen) {
+                return Some(args);
+            }
+            if !self.eat(&Token::Comma) {
+                return None;
+            }
+        }
+    }
+}
+
+fn constant(name: &str) -> Option<f64> {
+    match name {
+        "pi" | "π" => Some(std::f64::consts::PI),
+        "tau" | "τ" => Some(std::f64::consts::TAU),
+        "e" => Some(std::f64::consts::E),
+        _ => None,
+    }
+}
+
+// Trig functions work in radians
+fn call_function(name: &str, args: &[f64]) -> Option<f64> {
+    match (name, args) {
+        ("sqrt", [x]) => Some(x.sqrt()),
+        ("cbrt", [x]) => Some(x.cbrt()),
+        ("abs", [x]) => Some(x.abs()),
+        ("sin", [x]) => Some(x.sin()),
+        ("cos", [x]) => Some(x.cos()),
+        ("tan", [x]) => Some(x.tan()),
+        ("asin", [x]) => Some(x.asin()),
+        ("acos", [x]) => Some(x.acos()),
+        ("atan", [x]) => Some(x.atan()),
+        ("atan2", [y, x]) => Some(y.atan2(*x)),
+        ("ln", [x]) => Some(x.ln()),
+        ("log" | "log10", [x]) => Some(x.log10()),
+        ("log", [x, base]) => Some(x.log(*base)),
+        ("log2", [x]) => Some(x.log2()),
+        ("exp", [x]) => Some(x.exp()),
+        ("pow", [x, y]) => Some(x.powf(*y)),
+        ("floor", [x]) => Some(x.floor()),
+        ("ceil", [x]) => Some(x.ceil()),
+        ("round", [x]) => Some(x.round()),
+        ("trunc", [x]) => Some(x.trunc()),
+        ("min", [first, rest @ ..]) => Some(rest.iter().fold(*first, |a, b| a.min(*b))),
+        ("max", [first, rest @ ..]) => Some(rest.iter().fold(*first, |a, b| a.max(*b))),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn value(expression: &str) -> f64 {
+        evaluate(expression)
+            .unwrap_or_else(|| panic!("{} didn't evaluate", expression))
+            .value
+    }
+
+    fn assert_close(expression: &str, expected: f64) {
+        let actual = value(expression);
+        assert!(
+            (actual - expected).abs() < 1e-12,
+            "{} gave {}, expected {}",
+            expression,
+            actual,
+            expected
+        );
+    }
+
+    #[test]
+    fn precedence() {
+        assert_eq!(value("2*(3+4)/7"), 2.0);
+        assert_eq!(value("1+2*3"), 7.0);
+        assert_eq!(value("(1+2)*3"), 9.0);
+        assert_eq!(value("10-4-3"), 3.0);
+        assert_eq!(value("7%4*2"), 6.0);
+        assert_eq!(value("2*3^2"), 18.0);
+    }
+
+    #[test]
+    fn powers_group_to_the_right() {
+        assert_eq!(value("2^3^2"), 512.0);
+        assert_eq!(value("2**10"), 1024.0);
+        assert_eq!(value("2^-1"), 0.5);
+    }
+
+    #[test]
+    fn minus_binds_looser_than_powers() {
+        assert_eq!(value("-2^2"), -4.0);
+        assert_eq!(value("(-2)^2"), 4.0);
+        assert_eq!(value("2*-3"), -6.0);
+    }
+
+    #[test]
+    fn other_bases() {
+        assert_eq!(value("0xff"), 255.0);
+        assert_eq!(value("0b101"), 5.0);
+        assert_eq!(value("0o17"), 15.0);
+        assert_eq!(value("0xff + 0b101"), 260.0);
+        assert_eq!(value("0b1111_0000"), 240.0);
+    }
+
+    #[test]
+    fn functions_and_constants() {
+        assert_close("sqrt(2)", std::f64::consts::SQRT_2);
+        assert_close("sin(pi/2)", 1.0);
+        assert_close("cos(0)", 1.0);
+        assert_close("ln(e)", 1.0);
+        assert_eq!(value("log(1000)"), 3.0);
+        assert_eq!(value("log(8, 2)"), 3.0);
+        assert_eq!(value("abs(-3)"), 3.0);
+        assert_eq!(value("floor(2.7) + ceil(2.2)"), 5.0);
+        assert_eq!(value("max(1, 5, 3)"), 5.0);
+        assert_eq!(value("min(4, 2)"), 2.0);
+        assert_eq!(value("pow(2, 8)"), 256.0);
+        assert!(evaluate("sqrt(1, 2)").is_none());
+        assert!(evaluate("nope(1)").is_none());
+    }
+
+    #[test]
+    fn rejects_what_isnt_a_sum() {
+        assert!(evaluate("1/0").is_none());
+        assert!(evaluate("firefox").is_none());
+        assert!(evaluate("2(3)").is_none());
+        assert!(evaluate("(1+2").is_none());
+        assert!(evaluate("1+").is_none());
+        assert!(evaluate("").is_none());
+    }
+
+    #[test]
+    fn bare_numbers_arent_computed() {
+        for expression in ["5", "-5", "2.5", "1e3", "pi", "(5)"] {
+            assert!(!evaluate(expression).unwrap().computed, "{}", expression);
+        }
+        for expression in ["1+1", "0xff", "sqrt(4)", "2^2"] {
+            assert!(evaluate(expression).unwrap().computed, "{}", expression);
+        }
+    }
+
+    #[test]
+    fn deep_nesting_gives_up_instead_of_overflowing() {
+        assert!(evaluate(&"(".repeat(100_000)).is_none());
+        assert!(evaluate(&"-".repeat(100_000)).is_none());
+        assert!(evaluate(&format!("{}1{}", "(".repeat(100), ")".repeat(100))).is_some());
+    }
+
+    #[test]
+    fn formatting() {
+        assert_eq!(format_number(2.0), "2");
+        assert_eq!(format_number(0.1 + 0.2), "0.3");
+        assert_eq!(format_number(1.0 / 3.0), "0.3333333333");
+        assert_eq!(format_number(3e20), "3e20");
+        assert_eq!(format_number(2e-7), "2e-7");
+    }
+}
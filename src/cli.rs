@@ -0,0 +1,152 @@
+use std::ops::ControlFlow;
+
+use gtk4::{
+    gio::{
+        ApplicationCommandLine,
+        prelude::{ApplicationCommandLineExt, ApplicationExt},
+    },
+    glib::{self, OptionArg, OptionFlags, VariantDict, variant::ToVariant},
+    prelude::{GtkApplicationExt, WidgetExt},
+};
+use libadwaita::Application;
+
+use crate::{
+    config::Settings,
+    history::{LaunchHistory, RunHistory},
+    providers::{APPS_PREFIX, FILES_PREFIXES, RUN_PREFIX, files_mode_query, is_path_prefix},
+};
+
+// Command line options. These get forwarded to the running instance by GApplication,
+// so compositor keybinds can run e.g. `wlshud --mode files` to open straight into browsing.
+pub fn add_cli_options(app: &Application) {
+    app.add_main_option(
+        "search",
+        glib::Char::from(b's'),
+        OptionFlags::NONE,
+        OptionArg::String,
+        "Open with this search already typed in",
+        Some("QUERY"),
+    );
+    app.add_main_option(
+        "mode",
+        glib::Char::from(b'm'),
+        OptionFlags::NONE,
+        OptionArg::String,
+        "Open in a search mode: files, run or apps",
+        Some("MODE"),
+    );
+    app.add_main_option(
+        "shortcut",
+        glib::Char::from(b'k'),
+        OptionFlags::NONE,
+        OptionArg::String,
+        "Trigger the shortcut at this path of characters",
+        Some("PATH"),
+    );
+    app.add_main_option(
+        "toggle",
+        glib::Char::from(b't'),
+        OptionFlags::NONE,
+        OptionArg::None,
+        "Close wlshud if it is open, otherwise open it",
+        None,
+    );
+    app.add_main_option(
+        "clear-history",
+        glib::Char::from(0),
+        OptionFlags::NONE,
+        OptionArg::None,
+        "Forget which apps and commands were launched, resetting search ranking",
+        None,
+    );
+    // handled in main before the application is built, only here so it shows up in --help
+    app.add_main_option(
+        "dmenu",
+        glib::Char::from(b'd'),
+        OptionFlags::NONE,
+        OptionArg::None,
+        "Pick a line from stdin and print it, like dmenu (-p PROMPT sets the placeholder)",
+        None,
+    );
+}
+
+// Runs in the process that was invoked, before anything gets forwarded to the primary instance,
+// so errors end up in the right terminal.
+pub fn check_local_options(_: &Application, options: &VariantDict) -> ControlFlow<glib::ExitCode> {
+    if let Some(mode) = options.lookup::<String>("mode").ok().flatten()
+        && !["files", "run", "apps"].contains(&mode.as_str())
+    {
+        eprintln!("Unknown mode '{}', expected files, run or apps", mode);
+        return ControlFlow::Break(glib::ExitCode::FAILURE);
+    }
+    ControlFlow::Continue(())
+}
+
+// Runs in the primary instance, whether the options were passed to it or to a second one.
+pub fn handle_command_line(app: &Application, cmdline: &ApplicationCommandLine) -> glib::ExitCode {
+    let options = cmdline.options_dict();
+
+    if options.contains("clear-history") {
+        if let Some(window) = app.windows().first() {
+            let _ = window.activate_action("wlshud.clear-history", None);
+        } else {
+            LaunchHistory::default().clear();
+            RunHistory::default().clear();
+        }
+        return glib::ExitCode::SUCCESS;
+    }
+
+    if options.contains("toggle")
+        && let Some(window) = app.windows().first()
+    {
+        let _ = window.activate_action("wlshud.toggle", None);
+        return glib::ExitCode::SUCCESS;
+    }
+
+    // builds the window or shows the existing one
+    app.activate();
+    let Some(window) = app.windows().first().cloned() else {
+        return glib::ExitCode::FAILURE;
+    };
+
+    let search = options
+        .lookup::<String>("search")
+        .ok()
+        .flatten()
+        .unwrap_or_default();
+    let mode = options.lookup::<String>("mode").ok().flatten();
+    // the window works out the prefix, with the settings it was opened with
+    if let Some(mode) = mode {
+        let _ = window.activate_action("wlshud.open-mode", Some(&(mode, search).to_variant()));
+    } else if !search.is_empty() {
+        let _ = window.activate_action("wlshud.set-query", Some(&search.to_variant()));
+    }
+
+    if let Some(path) = options.lookup::<String>("shortcut").ok().flatten() {
+        let _ = window.activate_action("wlshud.run-shortcut", Some(&path.to_variant()));
+    }
+
+    glib::ExitCode::SUCCESS
+}
+
+// The query --mode opens with, prefixes can be remapped or turned off in settings.json
+pub fn mode_query(settings: &Settings, mode: &str, search: &str) -> Result<String, String> {
+    let mode_prefix = match mode {
+        "files" => settings
+            .search_prefixes_for("files", &FILES_PREFIXES)
+            .and_then(|prefixes| files_mode_query(&prefixes)),
+        "run" => settings.search_prefix("run", RUN_PREFIX),
+        "apps" => settings.search_prefix("apps", APPS_PREFIX),
+        // anything else was already rejected by check_local_options
+        _ => Some(String::new()),
+    }
+    .ok_or_else(|| format!("{} search is turned off in settings.json", mode))?;
+    // don't double up the prefix if the query already has one, e.g. --mode files --search /etc
+    if (mode == "files" && is_path_prefix(search) && is_path_prefix(&mode_prefix))
+        || (!mode_prefix.is_empty() && search.starts_with(&mode_prefix))
+    {
+        Ok(search.to_owned())
+    } else {
+        Ok(format!("{}{}", mode_prefix, search))
+    }
+}
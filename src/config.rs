@@ -1,22 +1,36 @@
 use std::{
-    fs::{create_dir, read_to_string},
-    io,
-    path::PathBuf,
+    cmp::Reverse,
+    collections::HashMap,
+    fmt,
+    fs::{self, File, create_dir, read_to_string},
+    io::{self, Write},
+    path::{Path, PathBuf},
     str::Chars,
+    time::{Duration, SystemTime, UNIX_EPOCH},
 };
 
-use gtk4::glib::user_config_dir;
+use gtk4::glib::{self, user_cache_dir, user_config_dir};
 use json::JsonValue;
 
+use crate::providers::BUILT_IN_PROVIDERS;
+
 pub struct ConfigData {
     shortcuts_list: Vec<ShortcutNode>,
+    shortcuts_errors: Vec<ConfigError>,
+    settings: Settings,
+    settings_errors: Vec<ConfigError>,
 }
 
 impl Default for ConfigData {
     fn default() -> Self {
         // attempts to load config data first, then defaults
+        let (shortcuts_list, shortcuts_errors) = load_shortcuts_from_config();
+        let (settings, settings_errors) = load_settings_from_config();
         Self {
-            shortcuts_list: load_shortcuts_from_config(),
+            shortcuts_list,
+            shortcuts_errors,
+            settings,
+            settings_errors,
         }
     }
 }
@@ -25,69 +39,673 @@ impl ConfigData {
     pub fn shortcuts_list(&self) -> &[ShortcutNode] {
         &self.shortcuts_list
     }
+
+    pub fn shortcuts_errors(&self) -> &[ConfigError] {
+        &self.shortcuts_errors
+    }
+
+    pub fn settings(&self) -> &Settings {
+        &self.settings
+    }
+
+    pub fn settings_errors(&self) -> &[ConfigError] {
+        &self.settings_errors
+    }
+}
+
+// General settings loaded from settings.json. Any key missing from the file
+// (or with the wrong type) falls back to the default below.
+#[derive(Clone)]
+pub struct Settings {
+    pub max_search_results: usize,
+    pub app_margin: i32,
+    pub fade_duration: Duration,
+    pub notes_save_delay: Duration,
+    pub media_poll_interval: Duration,
+    // keep running in the background after closing so the next open is instant
+    pub daemon_mode: bool,
+    // how much launch history boosts app search results, 0 turns it off
+    pub frecency_weight: f32,
+    // desktop file ids (e.g. org.gnome.Nautilus) always shown or never shown in app search,
+    // regardless of NoDisplay, OnlyShowIn and the like
+    pub app_include: Vec<String>,
+    pub app_exclude: Vec<String>,
+    // what Terminal=true apps get run in, their command is added to the end
+    pub terminal: Vec<String>,
+    // launches that fail within this long get reported instead of closing the HUD
+    pub launch_check_time: Duration,
+    // search provider names to the query prefixes that pick them, replacing their defaults.
+    // None turns the provider off.
+    pub search_prefixes: HashMap<String, Option<Vec<String>>>,
+    // external programs that answer searches, see ScriptProvider
+    pub script_providers: Vec<ScriptProviderConfig>,
+}
+
+#[derive(Clone)]
+pub struct ScriptProviderConfig {
+    // also what it's called in search_prefixes
+    pub name: String,
+    pub exec: Vec<String>,
+    pub prefixes: Vec<String>,
+    // it gets killed if it's still going after this long
+    pub timeout: Duration,
+}
+
+impl ScriptProviderConfig {
+    const DEFAULT_TIMEOUT: Duration = Duration::from_secs(1);
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            max_search_results: 20,
+            app_margin: 32,
+            fade_duration: Duration::from_millis(250),
+            // same length as the close animation so notes get saved before closing
+            notes_save_delay: Duration::from_millis(250),
+            media_poll_interval: Duration::from_secs(1),
+            daemon_mode: false,
+            frecency_weight: 0.5,
+            app_include: Vec::new(),
+            app_exclude: Vec::new(),
+            terminal: match std::env::var("TERMINAL") {
+                Ok(terminal) if !terminal.is_empty() => vec![terminal, "-e".to_owned()],
+                _ => vec!["xdg-terminal-exec".to_owned()],
+            },
+            launch_check_time: Duration::from_millis(250),
+            search_prefixes: HashMap::new(),
+            script_providers: Vec::new(),
+        }
+    }
+}
+
+impl Settings {
+    // The prefixes that pick the named search provider, None when it's turned off
+    pub fn search_prefixes_for(&self, provider: &str, defaults: &[&str]) -> Option<Vec<String>> {
+        match self.search_prefixes.get(provider) {
+            Some(prefixes) => prefixes.clone(),
+            None => Some(defaults.iter().map(|p| p.to_string()).collect()),
+        }
+    }
+
+    // The first prefix that picks the named search provider, for opening straight into it
+    pub fn search_prefix(&self, provider: &str, default: &str) -> Option<String> {
+        self.search_prefixes_for(provider, &[default])?
+            .into_iter()
+            .next()
+    }
+}
+
+// Settings that can't be used fall back to their defaults, with an error saying why.
+// A broken settings.json is left alone so it can be fixed, only a missing one gets written.
+pub fn load_settings_from_config() -> (Settings, Vec<ConfigError>) {
+    let path = settings_file_path();
+    match read_to_string(&path) {
+        Ok(s) => match json::parse(&s) {
+            Ok(parsed) => {
+                let mut errors = Vec::new();
+                let settings = parse_settings_json(&parsed, &mut errors);
+                (settings, errors)
+            }
+            Err(e) => (
+                Settings::default(),
+                vec![ConfigError::from_json_error(e, "settings.json", &s)],
+            ),
+        },
+        Err(e) if e.kind() != io::ErrorKind::NotFound => (
+            Settings::default(),
+            vec![ConfigError::Io {
+                file: "settings.json",
+                error: e,
+            }],
+        ),
+        Err(_) => {
+            // write out the defaults so there's something to edit
+            let defaults = Settings::default();
+            let _ = write_config_file(
+                &path,
+                &settings_to_json(&defaults).pretty(4),
+                Duration::ZERO,
+            );
+            (defaults, Vec::new())
+        }
+    }
+}
+
+pub fn parse_settings_json(data: &JsonValue, errors: &mut Vec<ConfigError>) -> Settings {
+    let defaults = Settings::default();
+    const MILLISECONDS: &str = "a whole number of milliseconds";
+    let millis = |v: &JsonValue| v.as_u64().map(Duration::from_millis);
+    Settings {
+        max_search_results: read_setting(
+            data,
+            "max_search_results",
+            "a whole number, 0 or more",
+            errors,
+            JsonValue::as_usize,
+        )
+        .unwrap_or(defaults.max_search_results),
+        app_margin: read_setting(
+            data,
+            "app_margin",
+            "a whole number of pixels, 0 or more",
+            errors,
+            |v| v.as_i32().filter(|m| *m >= 0),
+        )
+        .unwrap_or(defaults.app_margin),
+        fade_duration: read_setting(data, "fade_duration_ms", MILLISECONDS, errors, millis)
+            .unwrap_or(defaults.fade_duration),
+        notes_save_delay: read_setting(data, "notes_save_delay_ms", MILLISECONDS, errors, millis)
+            .unwrap_or(defaults.notes_save_delay),
+        media_poll_interval: read_setting(
+            data,
+            "media_poll_interval_ms",
+            MILLISECONDS,
+            errors,
+            millis,
+        )
+        .unwrap_or(defaults.media_poll_interval),
+        daemon_mode: read_setting(data, "daemon_mode", "true or false", errors, |v| {
+            v.as_bool()
+        })
+        .unwrap_or(defaults.daemon_mode),
+        frecency_weight: read_setting(
+            data,
+            "frecency_weight",
+            "a number, 0 or more",
+            errors,
+            |v| v.as_f32().filter(|w| w.is_finite() && *w >= 0.0),
+        )
+        .unwrap_or(defaults.frecency_weight),
+        app_include: read_setting(
+            data,
+            "app_include",
+            "a list of desktop file ids",
+            errors,
+            parse_app_ids,
+        )
+        .unwrap_or(defaults.app_include),
+        app_exclude: read_setting(
+            data,
+            "app_exclude",
+            "a list of desktop file ids",
+            errors,
+            parse_app_ids,
+        )
+        .unwrap_or(defaults.app_exclude),
+        terminal: read_setting(
+            data,
+            "terminal",
+            "a list of the command's arguments",
+            errors,
+            |v| {
+                v.members()
+                    .map(|m| m.as_str().map(|s| s.to_owned()))
+                    .collect::<Option<Vec<String>>>()
+                    .filter(|t| !t.is_empty())
+            },
+        )
+        .unwrap_or(defaults.terminal),
+        launch_check_time: read_setting(data, "launch_check_time_ms", MILLISECONDS, errors, millis)
+            .unwrap_or(defaults.launch_check_time),
+        search_prefixes: read_setting(
+            data,
+            "search_prefixes",
+            "an object of search provider names to prefixes",
+            errors,
+            |v| v.is_object().then_some(v),
+        )
+        .map(|v| parse_search_prefixes(v, errors))
+        .unwrap_or(defaults.search_prefixes),
+        script_providers: read_setting(
+            data,
+            "script_providers",
+            "a list of script providers",
+            errors,
+            |v| v.is_array().then_some(v),
+        )
+        .map(|v| parse_script_providers(v, errors))
+        .unwrap_or(defaults.script_providers),
+    }
+}
+
+// A missing key quietly means the default. One that's there but can't be used gets an error
+// saying what it should be, since otherwise it'd look like the setting just does nothing.
+fn read_setting<'a, T>(
+    data: &'a JsonValue,
+    key: &str,
+    expected: &str,
+    errors: &mut Vec<ConfigError>,
+    parse: impl FnOnce(&'a JsonValue) -> Option<T>,
+) -> Option<T> {
+    let value = &data[key];
+    if value.is_null() {
+        return None;
+    }
+    let parsed = parse(value);
+    if parsed.is_none() {
+        errors.push(ConfigError::InvalidSetting {
+            key: key.to_owned(),
+            message: format!("should be {}, using the default instead", expected),
+        });
+    }
+    parsed
+}
+
+// Entries without a name, a command or a prefix are skipped with an error, and so are ones
+// named after another provider since search_prefixes couldn't tell them apart. The command is a
+// list of arguments or one command line, same as shortcuts.
+fn parse_script_providers(
+    data: &JsonValue,
+    errors: &mut Vec<ConfigError>,
+) -> Vec<ScriptProviderConfig> {
+    let mut providers: Vec<ScriptProviderConfig> = Vec::new();
+    for (idx, member) in data.members().enumerate() {
+        let mut skip = |message: String| {
+            errors.push(ConfigError::InvalidSetting {
+                key: format!("script_providers[{}]", idx),
+                message,
+            })
+        };
+        let Some(name) = member["name"].as_str() else {
+            skip("it needs a \"name\"".to_owned());
+            continue;
+        };
+        if BUILT_IN_PROVIDERS.contains(&name) || providers.iter().any(|p| p.name == name) {
+            skip(format!("there's already a search provider called {}", name));
+            continue;
+        }
+        let exec = if let Some(command_line) = member["exec"].as_str() {
+            match parse_command_line(command_line) {
+                Ok(exec) => exec,
+                Err(e) => {
+                    skip(format!("bad \"exec\": {}", e));
+                    continue;
+                }
+            }
+        } else {
+            member["exec"]
+                .members()
+                .filter_map(|m| m.as_str())
+                .map(|arg| arg.to_owned())
+                .collect()
+        };
+        if exec.is_empty() {
+            skip("it needs a command to run in \"exec\"".to_owned());
+            continue;
+        }
+        let prefixes = if let Some(prefix) = member["prefix"].as_str() {
+            vec![prefix.to_owned()]
+        } else {
+            member["prefix"]
+                .members()
+                .filter_map(|m| m.as_str())
+                .map(|p| p.to_owned())
+                .collect()
+        };
+        if prefixes.is_empty() {
+            skip("it needs a \"prefix\" to pick it, \"\" for every query".to_owned());
+            continue;
+        }
+        let timeout = read_setting(
+            member,
+            "timeout_ms",
+            "a whole number of milliseconds",
+            errors,
+            |v| v.as_u64().map(Duration::from_millis),
+        );
+        providers.push(ScriptProviderConfig {
+            name: name.to_owned(),
+            exec,
+            prefixes,
+            timeout: timeout.unwrap_or(ScriptProviderConfig::DEFAULT_TIMEOUT),
+        });
+    }
+    providers
+}
+
+// Each provider gets a prefix, a list of them, or false to turn it off
+fn parse_search_prefixes(
+    data: &JsonValue,
+    errors: &mut Vec<ConfigError>,
+) -> HashMap<String, Option<Vec<String>>> {
+    let mut prefixes = HashMap::new();
+    for (name, value) in data.entries() {
+        let provider_prefixes = if let Some(prefix) = value.as_str() {
+            Some(vec![prefix.to_owned()])
+        } else if value.as_bool() == Some(false) {
+            None
+        } else if let Some(list) = value
+            .members()
+            .map(|m| m.as_str().map(|p| p.to_owned()))
+            .collect::<Option<Vec<String>>>()
+            .filter(|_| value.is_array())
+        {
+            Some(list)
+        } else {
+            errors.push(ConfigError::InvalidSetting {
+                key: format!("search_prefixes.{}", name),
+                message: "should be a prefix, a list of them or false, using the default instead"
+                    .to_owned(),
+            });
+            continue;
+        };
+        prefixes.insert(name.to_owned(), provider_prefixes);
+    }
+    prefixes
+}
+
+// Accepts ids with or without the .desktop extension
+fn parse_app_ids(data: &JsonValue) -> Option<Vec<String>> {
+    if !data.is_array() {
+        return None;
+    }
+    data.members()
+        .map(|m| {
+            m.as_str()
+                .map(|id| id.strip_suffix(".desktop").unwrap_or(id).to_owned())
+        })
+        .collect()
+}
+
+fn settings_to_json(settings: &Settings) -> JsonValue {
+    json::object! {
+        max_search_results: settings.max_search_results,
+        app_margin: settings.app_margin,
+        fade_duration_ms: settings.fade_duration.as_millis() as u64,
+        notes_save_delay_ms: settings.notes_save_delay.as_millis() as u64,
+        media_poll_interval_ms: settings.media_poll_interval.as_millis() as u64,
+        daemon_mode: settings.daemon_mode,
+        frecency_weight: settings.frecency_weight,
+        app_include: settings.app_include.clone(),
+        app_exclude: settings.app_exclude.clone(),
+        terminal: settings.terminal.clone(),
+        launch_check_time_ms: settings.launch_check_time.as_millis() as u64,
+        search_prefixes: settings
+            .search_prefixes
+            .iter()
+            .map(|(name, prefixes)| {
+                let value = match prefixes {
+                    Some(prefixes) => JsonValue::from(prefixes.clone()),
+                    None => JsonValue::Boolean(false),
+                };
+                (name.as_str(), value)
+            })
+            .collect::<json::object::Object>(),
+        script_providers: settings
+            .script_providers
+            .iter()
+            .map(|provider| {
+                json::object! {
+                    name: provider.name.clone(),
+                    exec: provider.exec.clone(),
+                    prefix: provider.prefixes.clone(),
+                    timeout_ms: provider.timeout.as_millis() as u64,
+                }
+            })
+            .collect::<Vec<JsonValue>>(),
+    }
 }
 
 #[derive(Clone)]
 pub struct ShortcutNode {
     pub character: char,
+    // for shell shortcuts this is always a single command line
     pub exec: Option<Vec<String>>,
     pub children: Vec<ShortcutNode>,
     pub icon: Option<String>,
+    // run through sh -c, so pipes, redirects and the like work
+    pub shell: bool,
+}
+
+impl ShortcutNode {
+    // What actually gets run
+    pub fn command(&self) -> Option<Vec<String>> {
+        let exec = self.exec.as_ref()?;
+        if self.shell {
+            Some(vec!["sh".to_owned(), "-c".to_owned(), exec.join(" ")])
+        } else {
+            Some(exec.clone())
+        }
+    }
+}
+
+// Splits a command line into arguments the way a POSIX shell would, with quotes and escapes
+pub fn parse_command_line(command_line: &str) -> Result<Vec<String>, glib::Error> {
+    Ok(glib::shell_parse_argv(command_line)?
+        .into_iter()
+        .map(|arg| arg.to_string_lossy().to_string())
+        .collect())
 }
 
-pub fn load_shortcuts_from_config() -> Vec<ShortcutNode> {
+// Problems found while loading shortcuts.json or settings.json. These get shown to the user
+// instead of silently dropping shortcuts or settings.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io {
+        // which config file, e.g. shortcuts.json
+        file: &'static str,
+        error: io::Error,
+    },
+    Syntax {
+        file: &'static str,
+        line: usize,
+        column: usize,
+        message: String,
+    },
+    InvalidNode {
+        // e.g. [2].children[0]
+        node_path: String,
+        message: String,
+    },
+    RefusingToOverwrite,
+    InvalidSetting {
+        // e.g. script_providers[1]
+        key: String,
+        message: String,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io { file, error } => write!(f, "Could not read {}: {}", file, error),
+            ConfigError::Syntax {
+                file,
+                line,
+                column,
+                message,
+            } => write!(f, "{} line {}, column {}: {}", file, line, column, message),
+            ConfigError::InvalidNode { node_path, message } => {
+                write!(f, "shortcuts.json node {}: {}", node_path, message)
+            }
+            ConfigError::RefusingToOverwrite => write!(
+                f,
+                "Not saving shortcuts: shortcuts.json has errors, fix it by hand first"
+            ),
+            ConfigError::InvalidSetting { key, message } => {
+                write!(f, "settings.json {}: {}", key, message)
+            }
+        }
+    }
+}
+
+impl ConfigError {
+    fn from_json_error(e: json::Error, file: &'static str, source: &str) -> Self {
+        match e {
+            json::Error::UnexpectedCharacter { ch, line, column } => ConfigError::Syntax {
+                file,
+                line,
+                column,
+                message: format!("unexpected character '{}'", ch),
+            },
+            // the json crate doesn't give a position for these, so point at the end of the file
+            other => ConfigError::Syntax {
+                file,
+                line: source.lines().count().max(1),
+                column: source
+                    .lines()
+                    .last()
+                    .map(|l| l.chars().count())
+                    .unwrap_or(0)
+                    + 1,
+                message: other.to_string(),
+            },
+        }
+    }
+}
+
+// Loads whatever shortcuts are valid, along with every error found on the way.
+// A syntax error means nothing could be loaded at all.
+pub fn load_shortcuts_from_config() -> (Vec<ShortcutNode>, Vec<ConfigError>) {
     let shortcuts_path = shortcuts_file_path();
-    let shortucts_file = read_to_string(shortcuts_path)
-        .and_then(|s| json::parse(&s).map_err(|_| io::Error::other("Failed to parse JSON")));
-    if let Ok(parsed) = shortucts_file {
-        parse_shortcuts_json(&parsed)
-    } else {
-        Vec::new()
+    let shortcuts_file = match read_to_string(shortcuts_path) {
+        Ok(s) => s,
+        // no shortcuts file yet is fine
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return (Vec::new(), Vec::new()),
+        Err(e) => {
+            return (
+                Vec::new(),
+                vec![ConfigError::Io {
+                    file: "shortcuts.json",
+                    error: e,
+                }],
+            );
+        }
+    };
+    match json::parse(&shortcuts_file) {
+        Ok(parsed) => {
+            let mut errors = Vec::new();
+            let nodes = parse_shortcuts_json(&parsed, "", &mut errors);
+            (nodes, errors)
+        }
+        Err(e) => (
+            Vec::new(),
+            vec![ConfigError::from_json_error(
+                e,
+                "shortcuts.json",
+                &shortcuts_file,
+            )],
+        ),
     }
 }
 
-pub fn parse_shortcuts_json(data: &JsonValue) -> Vec<ShortcutNode> {
+pub fn parse_shortcuts_json(
+    data: &JsonValue,
+    node_path: &str,
+    errors: &mut Vec<ConfigError>,
+) -> Vec<ShortcutNode> {
     let mut vec = Vec::new();
     if data.is_array() {
-        for member in data.members() {
+        for (idx, member) in data.members().enumerate() {
+            let member_path = format!("{}[{}]", node_path, idx);
             // build node from member
+            let shell = member["shell"].as_bool().unwrap_or(false);
             let exec_data = &member["exec"];
-            let exec = if exec_data.is_array() {
-                Some(
-                    exec_data
-                        .members()
-                        .map(|s| s.as_str().unwrap_or("").to_owned())
-                        .collect(),
-                )
+            let exec = if let Some(command_line) = exec_data.as_str() {
+                // a string works too, it's split up like a shell would unless it's run by one
+                if shell {
+                    Some(vec![command_line.to_owned()])
+                } else {
+                    match parse_command_line(command_line) {
+                        Ok(exec) => Some(exec),
+                        Err(e) => {
+                            errors.push(ConfigError::InvalidNode {
+                                node_path: member_path.clone(),
+                                message: format!("could not parse \"exec\": {}", e),
+                            });
+                            None
+                        }
+                    }
+                }
+            } else if exec_data.is_array() {
+                if exec_data.members().any(|s| !s.is_string()) {
+                    errors.push(ConfigError::InvalidNode {
+                        node_path: member_path.clone(),
+                        message: "\"exec\" must only contain strings".to_owned(),
+                    });
+                }
+                let args = exec_data
+                    .members()
+                    .map(|s| s.as_str().unwrap_or("").to_owned())
+                    .collect::<Vec<String>>();
+                // shell shortcuts are a single command line, quoted so spaces and $ in the
+                // arguments stay as they are
+                if shell {
+                    Some(vec![
+                        args.iter()
+                            .map(|arg| glib::shell_quote(arg).to_string_lossy().to_string())
+                            .collect::<Vec<String>>()
+                            .join(" "),
+                    ])
+                } else {
+                    Some(args)
+                }
             } else {
+                if !exec_data.is_null() {
+                    errors.push(ConfigError::InvalidNode {
+                        node_path: member_path.clone(),
+                        message: "\"exec\" must be a string or an array of strings".to_owned(),
+                    });
+                }
                 None
             };
             let character_data = &member["character"];
-            if !character_data.is_string() {
+            let Some(char_str) = character_data.as_str() else {
+                errors.push(ConfigError::InvalidNode {
+                    node_path: member_path,
+                    message: "missing \"character\" string, skipping this shortcut".to_owned(),
+                });
                 continue;
-            }
-            let char_str = character_data.to_string();
-            if char_str.is_empty() {
+            };
+            let Some(character) = char_str.chars().next() else {
+                errors.push(ConfigError::InvalidNode {
+                    node_path: member_path,
+                    message: "\"character\" is empty, skipping this shortcut".to_owned(),
+                });
                 continue;
-            }
-            let children = parse_shortcuts_json(&member["children"]);
+            };
+            let children = parse_shortcuts_json(
+                &member["children"],
+                &format!("{}.children", member_path),
+                errors,
+            );
             let node = ShortcutNode {
-                character: char_str.chars().next().unwrap(),
+                character,
                 exec,
                 children,
                 icon: member["icon"].as_str().map(|s| s.to_owned()),
+                shell,
             };
             vec.push(node);
         }
+    } else if !data.is_null() {
+        errors.push(ConfigError::InvalidNode {
+            node_path: if node_path.is_empty() {
+                "root".to_owned()
+            } else {
+                node_path.to_owned()
+            },
+            message: "expected an array of shortcuts".to_owned(),
+        });
     }
 
     vec
 }
 
-pub fn save_shortcuts_json(shortcuts: &[ShortcutNode]) {
+// Refuses to write if the file on disk currently has errors, since whatever was loaded
+// from it is missing the broken parts and saving would throw them away.
+pub fn save_shortcuts_json(shortcuts: &[ShortcutNode]) -> Result<(), ConfigError> {
+    let (_, errors) = load_shortcuts_from_config();
+    if !errors.is_empty() {
+        return Err(ConfigError::RefusingToOverwrite);
+    }
     let json_data = shortcut_array_to_json(shortcuts);
     let path = shortcuts_file_path();
-    let _ = std::fs::write(path, json_data.dump());
+    write_config_file(&path, &json_data.dump(), Duration::ZERO).map_err(|error| ConfigError::Io {
+        file: "shortcuts.json",
+        error,
+    })
 }
 
 fn shortcut_array_to_json(shortcuts: &[ShortcutNode]) -> JsonValue {
@@ -101,7 +719,12 @@ fn shortcut_array_to_json(shortcuts: &[ShortcutNode]) -> JsonValue {
         if let Some(icon) = &node.icon {
             obj["icon"] = JsonValue::String(icon.to_owned());
         }
-        if let Some(exec) = &node.exec {
+        if node.shell {
+            obj["shell"] = JsonValue::Boolean(true);
+            if let Some(exec) = &node.exec {
+                obj["exec"] = JsonValue::String(exec.join(" "));
+            }
+        } else if let Some(exec) = &node.exec {
             let mut exec_arr = json::array![];
             for cmd in exec {
                 let _ = exec_arr.push(JsonValue::String(cmd.to_owned()));
@@ -129,6 +752,7 @@ pub fn insert_shortcut_node(
                 exec: None,
                 children: Vec::new(),
                 icon: None,
+                shell: false,
             };
             insert_shortcut_node(char_path, to_insert, &mut new_node.children);
             into.push(new_node);
@@ -138,6 +762,19 @@ pub fn insert_shortcut_node(
     }
 }
 
+pub fn find_shortcut_node<'a>(
+    char_path: &mut Chars,
+    nodes: &'a mut [ShortcutNode],
+) -> Option<&'a mut ShortcutNode> {
+    let c = char_path.next()?;
+    let node = nodes.iter_mut().find(|n| n.character == c)?;
+    if char_path.as_str().is_empty() {
+        Some(node)
+    } else {
+        find_shortcut_node(char_path, &mut node.children)
+    }
+}
+
 pub fn delete_shortcut_node(char_path: &mut Chars, from: &mut Vec<ShortcutNode>) {
     // Will silently fail if passed an empty Chars
     if let Some(c) = char_path.next() {
@@ -189,6 +826,119 @@ fn delete_shortcut_node_recursion(
     }
 }
 
+const MAX_BACKUPS: usize = 10;
+// notes get saved after every bit of typing, so only back them up every so often
+pub const NOTES_BACKUP_INTERVAL: Duration = Duration::from_secs(5 * 60);
+
+pub struct Backup {
+    pub path: PathBuf,
+    // the config file this is a backup of
+    pub original: PathBuf,
+    pub time: SystemTime,
+}
+
+// Backs up the current version of the file, then writes to a temp file and renames it
+// over the original so a crash or full disk can never leave a half-written file.
+// No backup is made if the newest one is younger than backup_interval.
+pub fn write_config_file(path: &Path, contents: &str, backup_interval: Duration) -> io::Result<()> {
+    if path.exists() {
+        backup_config_file(path, backup_interval)?;
+    }
+    write_file_atomic(path, contents)
+}
+
+// Writes to a temp file and renames it over the original, without making a backup
+pub fn write_file_atomic(path: &Path, contents: &str) -> io::Result<()> {
+    let mut tmp_path = path.as_os_str().to_owned();
+    tmp_path.push(".tmp");
+    let mut tmp_file = File::create(&tmp_path)?;
+    tmp_file.write_all(contents.as_bytes())?;
+    tmp_file.sync_all()?;
+    fs::rename(&tmp_path, path)
+}
+
+fn backup_config_file(path: &Path, backup_interval: Duration) -> io::Result<()> {
+    let backups = list_backups(path);
+    if let Some(newest) = backups.first()
+        && newest
+            .time
+            .elapsed()
+            .is_ok_and(|elapsed| elapsed < backup_interval)
+    {
+        return Ok(());
+    }
+
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    // down to the nanosecond so two saves in the same second don't overwrite each other's backup
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    fs::copy(
+        path,
+        backups_dir().join(format!(
+            "{}.{}.{:09}",
+            file_name,
+            now.as_secs(),
+            now.subsec_nanos()
+        )),
+    )?;
+
+    // the one just made isn't in the list, so keep one less than the max
+    for old in backups.iter().skip(MAX_BACKUPS - 1) {
+        let _ = fs::remove_file(&old.path);
+    }
+    Ok(())
+}
+
+// Backups are stored as <file name>.<unix time>.<nanoseconds> in the backups dir, newest first.
+// Older ones without the nanoseconds are still listed.
+pub fn list_backups(path: &Path) -> Vec<Backup> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let mut backups = Vec::new();
+    if let Ok(iter) = fs::read_dir(backups_dir()) {
+        for entry in iter.flatten() {
+            let name = entry.file_name().to_string_lossy().into_owned();
+            if let Some(time) = name
+                .strip_prefix(file_name.as_ref())
+                .and_then(|s| s.strip_prefix('.'))
+                .and_then(parse_backup_time)
+            {
+                backups.push(Backup {
+                    path: entry.path(),
+                    original: path.to_owned(),
+                    time,
+                });
+            }
+        }
+    }
+    backups.sort_by_key(|b| Reverse(b.time));
+    backups
+}
+
+fn parse_backup_time(s: &str) -> Option<SystemTime> {
+    let (secs, nanos) = match s.split_once('.') {
+        Some((secs, nanos)) => (secs, nanos.parse::<u32>().ok()?),
+        None => (s, 0),
+    };
+    Some(UNIX_EPOCH + Duration::new(secs.parse::<u64>().ok()?, nanos))
+}
+
+// Restoring goes through write_config_file too, so the version being replaced
+// gets backed up and the restore can be undone.
+pub fn restore_backup(backup: &Backup) -> io::Result<()> {
+    let contents = read_to_string(&backup.path)?;
+    write_config_file(&backup.original, &contents, Duration::ZERO)
+}
+
+fn backups_dir() -> PathBuf {
+    let mut dir = wlshud_config_dir();
+    dir.push("backups");
+    if !dir.exists() {
+        let _ = create_dir(&dir);
+    }
+    dir
+}
+
 fn wlshud_config_dir() -> PathBuf {
     let mut dir = user_config_dir();
     dir.push("wlshud");
@@ -204,12 +954,41 @@ pub fn shortcuts_file_path() -> PathBuf {
     dir
 }
 
+pub fn settings_file_path() -> PathBuf {
+    let mut dir = wlshud_config_dir();
+    dir.push("settings.json");
+    dir
+}
+
+pub fn launch_history_file_path() -> PathBuf {
+    let mut dir = wlshud_config_dir();
+    dir.push("launch_history.json");
+    dir
+}
+
+pub fn run_history_file_path() -> PathBuf {
+    let mut dir = wlshud_config_dir();
+    dir.push("run_history.json");
+    dir
+}
+
 pub fn notes_file_path() -> PathBuf {
     let mut dir = wlshud_config_dir();
     dir.push("notes.txt");
     dir
 }
 
+// Output of everything launched, in the cache dir since it's fine to lose
+pub fn launch_logs_dir() -> PathBuf {
+    let mut dir = user_cache_dir();
+    dir.push("wlshud");
+    dir.push("launch-logs");
+    if !dir.exists() {
+        let _ = fs::create_dir_all(&dir);
+    }
+    dir
+}
+
 pub fn css_file_path() -> PathBuf {
     let mut dir = wlshud_config_dir();
     dir.push("style.css");
@@ -233,3 +1012,87 @@ pub fn flatten_shortcuts_list(
 
     flat_vec
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(settings: &str) -> (Settings, Vec<String>) {
+        let mut errors = Vec::new();
+        let settings = parse_settings_json(&json::parse(settings).unwrap(), &mut errors);
+        (settings, errors.iter().map(|e| e.to_string()).collect())
+    }
+
+    #[test]
+    fn shell_shortcut_arrays_keep_their_arguments() {
+        let mut errors = Vec::new();
+        let nodes = parse_shortcuts_json(
+            &json::parse(
+                r#"[{"character": "a", "shell": true, "exec": ["notify-send", "hello world", "$HOME"]},
+                    {"character": "b", "shell": true, "exec": "ls | wc -l"}]"#,
+            )
+            .unwrap(),
+            "",
+            &mut errors,
+        );
+        assert!(errors.is_empty());
+        assert_eq!(
+            nodes[0].command().unwrap(),
+            ["sh", "-c", "'notify-send' 'hello world' '$HOME'"]
+        );
+        assert_eq!(nodes[1].command().unwrap(), ["sh", "-c", "ls | wc -l"]);
+    }
+
+    #[test]
+    fn missing_settings_are_defaults_without_errors() {
+        let (settings, errors) = parse("{}");
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(settings.app_margin, Settings::default().app_margin);
+    }
+
+    #[test]
+    fn good_settings_are_used() {
+        let (settings, errors) = parse(
+            r#"{"app_margin": 0, "fade_duration_ms": 300, "daemon_mode": true,
+                "search_prefixes": {"files": false, "run": ["!", ">"]}}"#,
+        );
+        assert!(errors.is_empty(), "{:?}", errors);
+        assert_eq!(settings.app_margin, 0);
+        assert_eq!(settings.fade_duration, Duration::from_millis(300));
+        assert!(settings.daemon_mode);
+        assert_eq!(settings.search_prefixes["files"], None);
+        assert_eq!(
+            settings.search_prefixes["run"],
+            Some(vec!["!".to_owned(), ">".to_owned()])
+        );
+    }
+
+    #[test]
+    fn unusable_settings_are_reported_by_key() {
+        let (settings, errors) = parse(
+            r#"{"fade_duration_ms": "300", "daemon_mode": "yes", "app_margin": -4,
+                "app_include": ["firefox", 3], "search_prefixes": {"run": 1}}"#,
+        );
+        let defaults = Settings::default();
+        assert_eq!(settings.fade_duration, defaults.fade_duration);
+        assert_eq!(settings.daemon_mode, defaults.daemon_mode);
+        assert_eq!(settings.app_margin, defaults.app_margin);
+        assert!(settings.search_prefixes.is_empty());
+        for key in [
+            "fade_duration_ms",
+            "daemon_mode",
+            "app_margin",
+            "app_include",
+            "search_prefixes.run",
+        ] {
+            assert!(
+                errors
+                    .iter()
+                    .any(|e| e.starts_with(&format!("settings.json {}:", key))),
+                "no error for {} in {:?}",
+                key,
+                errors
+            );
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use gtk4::{
+    gio::{
+        DBusNodeInfo,
+        prelude::{ApplicationExt, ApplicationExtManual},
+    },
+    glib::{self, clone},
+    prelude::{GtkApplicationExt, GtkWindowExt, WidgetExt},
+};
+use libadwaita::Application;
+
+use crate::build_window;
+
+// Exported on the session bus next to GApplication's own interfaces, so scripts and status
+// bars can do e.g.
+// gdbus call --session --dest com.DrewCodesBadly.wlshud --object-path /com/DrewCodesBadly/wlshud \
+//     --method com.DrewCodesBadly.wlshud.Control.SetQuery "~/Documents"
+const CONTROL_INTERFACE_NAME: &str = "com.DrewCodesBadly.wlshud.Control";
+const CONTROL_INTERFACE_XML: &str = r#"
+<node>
+  <interface name="com.DrewCodesBadly.wlshud.Control">
+    <method name="Show"/>
+    <method name="Hide"/>
+    <method name="Toggle"/>
+    <method name="SetQuery">
+      <arg type="s" name="query" direction="in"/>
+    </method>
+    <method name="RunShortcut">
+      <arg type="s" name="path" direction="in"/>
+    </method>
+    <method name="ReloadConfig"/>
+  </interface>
+</node>
+"#;
+
+// Must be called after the application is registered (e.g. in startup). Every method maps onto
+// one of the wlshud.* window actions, opening the window first if needed.
+pub fn register_control_interface(app: &Application, reload_css: impl Fn() + 'static) {
+    let (Some(connection), Some(object_path)) = (app.dbus_connection(), app.dbus_object_path())
+    else {
+        return;
+    };
+    let Some(interface_info) = DBusNodeInfo::for_xml(CONTROL_INTERFACE_XML)
+        .ok()
+        .and_then(|info| info.lookup_interface(CONTROL_INTERFACE_NAME))
+    else {
+        return;
+    };
+
+    let registration = connection
+        .register_object(&object_path, &interface_info)
+        .method_call(clone!(
+            #[weak]
+            app,
+            move |_, _, _, _, method, parameters, invocation| {
+                let existing_window = app.windows().first().cloned();
+                match method {
+                    // activating shows the existing window or builds a new one
+                    "Show" => app.activate(),
+                    "Hide" => {
+                        if let Some(window) = existing_window {
+                            let _ = window.activate_action("wlshud.close", None);
+                        }
+                    }
+                    "Toggle" => {
+                        if let Some(window) = existing_window {
+                            let _ = window.activate_action("wlshud.toggle", None);
+                        } else {
+                            app.activate();
+                        }
+                    }
+                    "SetQuery" | "RunShortcut" => {
+                        app.activate();
+                        let action = if method == "SetQuery" {
+                            "wlshud.set-query"
+                        } else {
+                            "wlshud.run-shortcut"
+                        };
+                        if let Some(window) = app.windows().first() {
+                            let _ =
+                                window.activate_action(action, Some(&parameters.child_value(0)));
+                        }
+                    }
+                    "ReloadConfig" => {
+                        reload_css();
+                        // settings go into building the whole window, so build a new one with
+                        // them. It only gets shown if the old one was showing.
+                        if let Some(window) = existing_window {
+                            // the app would quit in between without a window to keep it going
+                            let _hold = app.hold();
+                            let was_visible = window.is_visible();
+                            window.destroy();
+                            let window = build_window(&app);
+                            if was_visible {
+                                let _ = window.activate_action("wlshud.show", None);
+                            }
+                        }
+                    }
+                    _ => {
+                        invocation.return_dbus_error(
+                            "org.freedesktop.DBus.Error.UnknownMethod",
+                            &format!("Unknown method {}", method),
+                        );
+                        return;
+                    }
+                }
+                invocation.return_value(None);
+            }
+        ))
+        .build();
+    if let Err(e) = registration {
+        eprintln!("Could not export the D-Bus control interface: {}", e);
+    }
+}
@@ -0,0 +1,243 @@
+use std::{cell::Cell, io, rc::Rc};
+
+use gtk4::{
+    ApplicationWindow, Box, ScrolledWindow, SearchEntry,
+    gio::{
+        ActionEntry, ApplicationFlags, SimpleActionGroup,
+        prelude::{ActionMapExtManual, ApplicationExt, ApplicationExtManual},
+    },
+    glib::{self, VariantTy, clone, variant::ToVariant},
+    prelude::{BoxExt, EditableExt, GtkApplicationExt, GtkWindowExt, WidgetExt},
+};
+use libadwaita::Application;
+
+use crate::{
+    APP_ID,
+    config::{Settings, load_settings_from_config},
+    init_overlay_window, load_style,
+    searching::{
+        ResultKind, SearchResult, add_result_keybinds, build_search_results, fuzzy_match_indices,
+        match_positions, selected_result_row,
+    },
+};
+
+// `wlshud --dmenu [-p PROMPT]` reads newline separated entries from stdin, lets the user pick
+// one with the usual search UI and prints it to stdout. Pressing enter when nothing matches
+// prints the typed query instead. Like dmenu, exits with 1 if nothing was picked.
+pub fn run_dmenu(prompt: Option<String>) -> glib::ExitCode {
+    let lines = io::stdin()
+        .lines()
+        .map_while(Result::ok)
+        .filter(|l| !l.is_empty())
+        .collect::<Vec<String>>();
+    let prompt = prompt.unwrap_or("Search...".to_owned());
+    let (settings, errors) = load_settings_from_config();
+    for error in errors {
+        eprintln!("{}", error);
+    }
+
+    let app = Application::builder()
+        .application_id(APP_ID)
+        .flags(ApplicationFlags::NON_UNIQUE)
+        .build();
+    let exit_code = Rc::new(Cell::new(glib::ExitCode::FAILURE));
+    app.connect_startup(|_| {
+        load_style();
+    });
+    app.connect_activate(clone!(
+        #[strong]
+        exit_code,
+        move |app| {
+            build_dmenu_window(app, &lines, &prompt, &settings, &exit_code);
+        }
+    ));
+    app.set_accels_for_action("wlshud.close", &["Escape"]);
+
+    // the real arguments are dmenu ones that GApplication doesn't know about
+    app.run_with_args(&["wlshud"]);
+    exit_code.get()
+}
+
+// Options that are followed by a value, which shouldn't be taken for -d even if it looks like it
+const OPTIONS_WITH_VALUES: [&str; 8] = [
+    "-s",
+    "--search",
+    "-m",
+    "--mode",
+    "-k",
+    "--shortcut",
+    "-p",
+    "--prompt",
+];
+
+// Some(the -p prompt, if any) when the arguments (without the program name) ask for dmenu mode.
+// Nothing after -- counts, like with GApplication's own options.
+pub fn dmenu_prompt_from_args(args: impl IntoIterator<Item = String>) -> Option<Option<String>> {
+    let mut dmenu = false;
+    let mut prompt = None;
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--" => break,
+            "-d" | "--dmenu" => dmenu = true,
+            "-p" | "--prompt" => prompt = args.next(),
+            option if OPTIONS_WITH_VALUES.contains(&option) => {
+                args.next();
+            }
+            _ => {}
+        }
+    }
+    dmenu.then_some(prompt)
+}
+
+fn build_dmenu_window(
+    app: &Application,
+    lines: &[String],
+    prompt: &str,
+    settings: &Settings,
+    exit_code: &Rc<Cell<glib::ExitCode>>,
+) {
+    let window = ApplicationWindow::new(app);
+    init_overlay_window(&window);
+
+    let entry = SearchEntry::builder()
+        .hexpand(true)
+        .valign(gtk4::Align::Start)
+        .placeholder_text(prompt)
+        .build();
+    let results_window = ScrolledWindow::builder().vexpand(true).build();
+    let outer_box = Box::builder()
+        .orientation(gtk4::Orientation::Vertical)
+        .margin_bottom(settings.app_margin)
+        .margin_top(settings.app_margin)
+        .margin_end(settings.app_margin)
+        .margin_start(settings.app_margin)
+        .build();
+    outer_box.append(&entry);
+    outer_box.append(&results_window);
+    window.set_child(Some(&outer_box));
+
+    // rows activate wlshud.exec with the line as the command, so print it instead of running it
+    let actions = SimpleActionGroup::new();
+    actions.add_action_entries([
+        ActionEntry::builder("exec")
+            .parameter_type(Some(VariantTy::STRING_ARRAY))
+            .activate(clone!(
+                #[weak]
+                app,
+                #[strong]
+                exit_code,
+                move |_, _, parameter| {
+                    if let Some(p) = parameter
+                        && let Some(picked) = p.get::<Vec<String>>()
+                        && let Some(line) = picked.first()
+                    {
+                        println!("{}", line);
+                        exit_code.set(glib::ExitCode::SUCCESS);
+                        app.quit();
+                    }
+                }
+            ))
+            .build(),
+        ActionEntry::builder("close")
+            .activate(clone!(
+                #[weak]
+                app,
+                move |_, _, _| {
+                    app.quit();
+                }
+            ))
+            .build(),
+    ]);
+    window.insert_action_group("wlshud", Some(&actions));
+
+    let lines = Rc::new(lines.to_vec());
+    let max_results = settings.max_search_results;
+    show_matches(&results_window, &lines, "", max_results);
+    entry.connect_text_notify(clone!(
+        #[strong]
+        lines,
+        #[weak]
+        results_window,
+        move |entry| {
+            show_matches(&results_window, &lines, &entry.text(), max_results);
+        }
+    ));
+
+    add_result_keybinds(&entry, &results_window);
+    entry.connect_activate(clone!(
+        #[weak]
+        results_window,
+        move |entry| {
+            if let Some(row) = selected_result_row(&results_window) {
+                row.activate();
+            } else {
+                let query = vec![entry.text().to_string()];
+                let _ = entry.activate_action("wlshud.exec", Some(&query.to_variant()));
+            }
+        }
+    ));
+
+    // close when unfocused
+    window.connect_is_active_notify(|window| {
+        if !window.is_active() {
+            let _ = <ApplicationWindow as WidgetExt>::activate_action(window, "wlshud.close", None);
+        }
+    });
+    window.present();
+}
+
+fn show_matches(results_window: &ScrolledWindow, lines: &[String], query: &str, max: usize) {
+    let to_result = |line: &String| SearchResult {
+        kind: ResultKind::Text,
+        icon_path: None,
+        name: line.clone(),
+        subtitle: None,
+        location: Default::default(),
+        execute_command: vec![line.clone()],
+        desktop_action: None,
+        actions: Vec::new(),
+        run_command: None,
+        match_positions: match_positions(query, line),
+        activation: None,
+    };
+    // keep the input order until something is typed, like dmenu
+    let results = if query.is_empty() {
+        lines.iter().take(max).map(to_result).collect()
+    } else {
+        let names = lines.iter().map(|l| l.as_str()).collect::<Vec<&str>>();
+        fuzzy_match_indices(query, &names, max)
+            .into_iter()
+            .map(|idx| to_result(&lines[idx]))
+            .collect()
+    };
+    results_window.set_child(Some(&build_search_results(results)));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prompt(args: &[&str]) -> Option<Option<String>> {
+        dmenu_prompt_from_args(args.iter().map(|a| a.to_string()))
+    }
+
+    #[test]
+    fn dmenu_flag_and_prompt() {
+        assert_eq!(prompt(&["-d"]), Some(None));
+        assert_eq!(
+            prompt(&["--dmenu", "-p", "Pick"]),
+            Some(Some("Pick".to_owned()))
+        );
+        assert_eq!(prompt(&["-p", "Pick", "-d"]), Some(Some("Pick".to_owned())));
+    }
+
+    #[test]
+    fn option_values_arent_the_flag() {
+        assert_eq!(prompt(&[]), None);
+        assert_eq!(prompt(&["-p", "-d"]), None);
+        assert_eq!(prompt(&["--search", "--dmenu"]), None);
+        assert_eq!(prompt(&["-s", "-d", "-m", "files"]), None);
+        assert_eq!(prompt(&["--", "-d"]), None);
+    }
+}
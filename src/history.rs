@@ -0,0 +1,203 @@
+use std::{
+    collections::HashMap,
+    fs::{self, read_to_string},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use json::JsonValue;
+
+use crate::{
+    config::{launch_history_file_path, run_history_file_path, write_file_atomic},
+    launcher::RunMode,
+};
+
+// older run prompt commands get dropped past this
+const MAX_RUN_HISTORY: usize = 200;
+
+// How many times each command was launched from wlshud and when it was last launched,
+// used to rank apps that get launched a lot above ones that match just as well.
+#[derive(Default)]
+pub struct LaunchHistory {
+    launches: HashMap<String, LaunchRecord>,
+}
+
+struct LaunchRecord {
+    count: u64,
+    // unix time in seconds
+    last_launched: u64,
+}
+
+impl LaunchHistory {
+    pub fn load() -> Self {
+        let mut launches = HashMap::new();
+        if let Ok(s) = read_to_string(launch_history_file_path())
+            && let Ok(parsed) = json::parse(&s)
+        {
+            for (key, record) in parsed.entries() {
+                if let (Some(count), Some(last_launched)) =
+                    (record["count"].as_u64(), record["last_launched"].as_u64())
+                {
+                    launches.insert(
+                        key.to_owned(),
+                        LaunchRecord {
+                            count,
+                            last_launched,
+                        },
+                    );
+                }
+            }
+        }
+        Self { launches }
+    }
+
+    pub fn record_launch(&mut self, exec: &[String]) {
+        let record = self
+            .launches
+            .entry(history_key(exec))
+            .or_insert(LaunchRecord {
+                count: 0,
+                last_launched: 0,
+            });
+        record.count += 1;
+        record.last_launched = unix_now();
+        self.save();
+    }
+
+    pub fn clear(&mut self) {
+        self.launches.clear();
+        let _ = fs::remove_file(launch_history_file_path());
+    }
+
+    // Scores commands between 0 and 1, relative to the most used one. Frequency is weighted by
+    // how recently the command was used, so something used a lot last year slowly fades out.
+    // The most used one is only looked for once, so get one of these per search rather than
+    // per command.
+    pub fn frecency_scorer(&self) -> impl Fn(&[String]) -> f32 + '_ {
+        let now = unix_now();
+        let max = self
+            .launches
+            .values()
+            .map(|r| r.frecency(now))
+            .fold(0.0, f32::max);
+        move |exec| {
+            if max <= 0.0 {
+                return 0.0;
+            }
+            self.launches
+                .get(&history_key(exec))
+                .map(|r| r.frecency(now) / max)
+                .unwrap_or(0.0)
+        }
+    }
+
+    fn save(&self) {
+        let mut obj = JsonValue::new_object();
+        for (key, record) in &self.launches {
+            obj[key.as_str()] = json::object! {
+                count: record.count,
+                last_launched: record.last_launched,
+            };
+        }
+        let _ = write_file_atomic(&launch_history_file_path(), &obj.dump());
+    }
+}
+
+impl LaunchRecord {
+    fn frecency(&self, now: u64) -> f32 {
+        const HOUR: u64 = 60 * 60;
+        const DAY: u64 = 24 * HOUR;
+        let age = now.saturating_sub(self.last_launched);
+        let recency_weight = if age < 4 * HOUR {
+            1.0
+        } else if age < DAY {
+            0.7
+        } else if age < 7 * DAY {
+            0.5
+        } else if age < 30 * DAY {
+            0.3
+        } else {
+            0.1
+        };
+        self.count as f32 * recency_weight
+    }
+}
+
+// Commands typed into the run prompt, most recent first, so they can be searched and run again
+#[derive(Default)]
+pub struct RunHistory {
+    entries: Vec<RunHistoryEntry>,
+}
+
+#[derive(Clone)]
+pub struct RunHistoryEntry {
+    pub command_line: String,
+    pub mode: RunMode,
+}
+
+impl RunHistory {
+    pub fn load() -> Self {
+        let mut entries = Vec::new();
+        if let Ok(s) = read_to_string(run_history_file_path())
+            && let Ok(parsed) = json::parse(&s)
+        {
+            for entry in parsed.members() {
+                if let (Some(command_line), Some(mode)) = (
+                    entry["command"].as_str(),
+                    entry["mode"].as_str().and_then(RunMode::parse),
+                ) {
+                    entries.push(RunHistoryEntry {
+                        command_line: command_line.to_owned(),
+                        mode,
+                    });
+                }
+            }
+        }
+        Self { entries }
+    }
+
+    // Moves the command to the top, running the same thing again doesn't add a second entry
+    pub fn record_run(&mut self, mode: RunMode, command_line: &str) {
+        self.entries
+            .retain(|e| !(e.mode == mode && e.command_line == command_line));
+        self.entries.insert(
+            0,
+            RunHistoryEntry {
+                command_line: command_line.to_owned(),
+                mode,
+            },
+        );
+        self.entries.truncate(MAX_RUN_HISTORY);
+        self.save();
+    }
+
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        let _ = fs::remove_file(run_history_file_path());
+    }
+
+    pub fn entries(&self) -> &[RunHistoryEntry] {
+        &self.entries
+    }
+
+    fn save(&self) {
+        let mut list = JsonValue::new_array();
+        for entry in &self.entries {
+            let _ = list.push(json::object! {
+                command: entry.command_line.as_str(),
+                mode: entry.mode.as_str(),
+            });
+        }
+        let _ = write_file_atomic(&run_history_file_path(), &list.dump());
+    }
+}
+
+fn history_key(exec: &[String]) -> String {
+    exec.join(" ")
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
@@ -0,0 +1,733 @@
+use std::{
+    fmt,
+    fs::{self, File},
+    io::{self, BufRead, BufReader, Read, Write},
+    os::unix::{fs::PermissionsExt, process::CommandExt},
+    path::{Path, PathBuf},
+    process::{Command, ExitStatus, Stdio},
+    sync::{Arc, Mutex},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use freedesktop_desktop_entry::{DesktopEntry, get_languages_from_env};
+use gtk4::{
+    gio,
+    glib::{self, user_runtime_dir},
+    prelude::FileExt,
+};
+
+use crate::config::{Settings, launch_logs_dir, parse_command_line};
+
+const MAX_LAUNCH_LOGS: usize = 50;
+
+// Turns desktop entries into commands the way the desktop entry spec describes: field codes
+// get expanded, Terminal=true apps get wrapped in the configured terminal and Path= becomes the
+// working directory.
+//
+// Everything launched is detached from wlshud: it gets its own session, is reparented away from
+// us (so there's nothing to reap and it outlives daemon mode restarts) and its output goes to a
+// log file in the cache dir. When a systemd user instance is running it also gets its own
+// transient scope, like apps launched from other desktop shells.
+pub struct Launcher {
+    terminal: Vec<String>,
+    systemd_scope: bool,
+    // how long launched programs are watched for failing
+    check_time: Duration,
+}
+
+#[derive(Debug)]
+pub enum LaunchError {
+    UnreadableEntry {
+        path: PathBuf,
+        message: String,
+    },
+    NoExec(PathBuf),
+    BadExec {
+        exec: String,
+        message: String,
+    },
+    EmptyCommand,
+    Spawn {
+        program: String,
+        error: io::Error,
+    },
+    // exited with an error before the check time was up
+    EarlyExit {
+        program: String,
+        code: i32,
+        // the last thing it printed, usually the reason
+        output: Option<String>,
+    },
+}
+
+impl fmt::Display for LaunchError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LaunchError::UnreadableEntry { path, message } => {
+                write!(f, "Could not read {}: {}", path.display(), message)
+            }
+            LaunchError::NoExec(path) => write!(f, "{} has nothing to run", path.display()),
+            LaunchError::BadExec { exec, message } => {
+                write!(f, "Could not parse the command '{}': {}", exec, message)
+            }
+            LaunchError::EmptyCommand => write!(f, "There is no command to run"),
+            LaunchError::Spawn { program, error } => {
+                write!(f, "Could not start {}: {}", program, error)
+            }
+            LaunchError::EarlyExit {
+                program,
+                code,
+                output,
+            } => {
+                write!(f, "{} exited with status {}", program, code)?;
+                if let Some(output) = output {
+                    write!(f, ": {}", output)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+// How a command typed into the run prompt gets run
+#[derive(Clone, Copy, PartialEq)]
+pub enum RunMode {
+    // split up with shell quoting rules and run directly
+    Direct,
+    // handed to sh -c, so pipes and redirects work
+    Shell,
+    // split up like Direct, but run inside the configured terminal
+    Terminal,
+    // handed to sh -c like Shell, with the output shown in the HUD instead of a log
+    Output,
+}
+
+impl RunMode {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            RunMode::Direct => "direct",
+            RunMode::Shell => "shell",
+            RunMode::Terminal => "terminal",
+            RunMode::Output => "output",
+        }
+    }
+
+    // whether the command line goes to sh -c as is instead of being split up
+    pub fn uses_shell(&self) -> bool {
+        matches!(self, RunMode::Shell | RunMode::Output)
+    }
+
+    pub fn parse(s: &str) -> Option<Self> {
+        match s {
+            "direct" => Some(RunMode::Direct),
+            "shell" => Some(RunMode::Shell),
+            "terminal" => Some(RunMode::Terminal),
+            "output" => Some(RunMode::Output),
+            _ => None,
+        }
+    }
+}
+
+impl Launcher {
+    pub fn new(settings: &Settings) -> Self {
+        Self {
+            terminal: settings.terminal.clone(),
+            systemd_scope: is_executable_in_path("systemd-run")
+                && user_runtime_dir().join("systemd/private").exists(),
+            check_time: settings.launch_check_time,
+        }
+    }
+
+    // A plain argument list, e.g. from a shortcut or the run prompt
+    pub async fn launch(&self, exec: &[String]) -> Result<(), LaunchError> {
+        let name = exec
+            .first()
+            .and_then(|program| Path::new(program).file_name())
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.spawn_detached(exec, None, &name).await
+    }
+
+    // What running `command_line` from the run prompt actually runs
+    pub fn run_argv(&self, mode: RunMode, command_line: &str) -> Result<Vec<String>, LaunchError> {
+        if mode.uses_shell() {
+            return Ok(vec![
+                "sh".to_owned(),
+                "-c".to_owned(),
+                command_line.to_owned(),
+            ]);
+        }
+        let argv = parse_command_line(command_line).map_err(|e| LaunchError::BadExec {
+            exec: command_line.to_owned(),
+            message: e.to_string(),
+        })?;
+        if mode == RunMode::Terminal && !self.terminal.is_empty() {
+            Ok(self.terminal.iter().cloned().chain(argv).collect())
+        } else {
+            Ok(argv)
+        }
+    }
+
+    // The desktop entry at `path`, or one of its [Desktop Action ...] groups, opening `files`
+    pub async fn launch_desktop_entry(
+        &self,
+        path: &Path,
+        action: Option<&str>,
+        files: &[PathBuf],
+    ) -> Result<(), LaunchError> {
+        let entry = read_desktop_entry(path)?;
+        let argv = self.desktop_entry_argv(&entry, action, files)?;
+        let working_dir = entry.path().filter(|d| !d.is_empty()).map(Path::new);
+        self.spawn_detached(&argv, working_dir, &entry.appid).await
+    }
+
+    // Everything launch_desktop_entry runs except the working directory, for storing in shortcuts
+    pub fn desktop_entry_argv(
+        &self,
+        entry: &DesktopEntry,
+        action: Option<&str>,
+        files: &[PathBuf],
+    ) -> Result<Vec<String>, LaunchError> {
+        let exec = match action {
+            Some(action) => entry.action_exec(action),
+            None => entry.exec(),
+        }
+        .ok_or(LaunchError::NoExec(entry.path.clone()))?;
+        let argv = expand_exec(exec, entry, files)?;
+        if entry.terminal() && !self.terminal.is_empty() {
+            Ok(self.terminal.iter().cloned().chain(argv).collect())
+        } else {
+            Ok(argv)
+        }
+    }
+
+    async fn spawn_detached(
+        &self,
+        argv: &[String],
+        working_dir: Option<&Path>,
+        name: &str,
+    ) -> Result<(), LaunchError> {
+        let (program, args) = argv.split_first().ok_or(LaunchError::EmptyCommand)?;
+        let mut cmd = if self.systemd_scope {
+            // systemd-run would be the one failing to find it, so check here to get a proper error
+            if !is_executable_in_path(program) {
+                return Err(LaunchError::Spawn {
+                    program: program.clone(),
+                    error: io::Error::new(io::ErrorKind::NotFound, "not found in PATH"),
+                });
+            }
+            let mut cmd = Command::new("systemd-run");
+            cmd.args(["--user", "--scope", "--quiet", "--collect"])
+                .arg(format!("--unit={}", scope_unit_name(name)))
+                .arg("--")
+                .arg(program);
+            cmd
+        } else {
+            Command::new(program)
+        };
+        cmd.args(args);
+        if let Some(dir) = working_dir {
+            cmd.current_dir(dir);
+        }
+
+        // a log that can't be created isn't worth failing the launch over
+        let log = open_launch_log(argv, name).ok();
+        let (stdout, stderr) = match &log {
+            Some((file, _)) => (
+                file.try_clone().map(Stdio::from).unwrap_or(Stdio::null()),
+                file.try_clone().map(Stdio::from).unwrap_or(Stdio::null()),
+            ),
+            None => (Stdio::null(), Stdio::null()),
+        };
+        cmd.stdin(Stdio::null()).stdout(stdout).stderr(stderr);
+
+        let check_ms = self.check_time.as_millis() as u32;
+        // SAFETY: detach only makes async-signal-safe calls
+        unsafe {
+            cmd.pre_exec(move || detach(check_ms));
+        }
+        // spawn() only returns once the check time is up, so don't block the UI on it
+        let (sender, receiver) = async_channel::bounded(1);
+        thread::spawn(move || {
+            let status = cmd.spawn().and_then(|mut child| child.wait());
+            let _ = sender.send_blocking(status);
+        });
+        match receiver.recv().await {
+            Ok(Err(error)) => Err(LaunchError::Spawn {
+                program: program.clone(),
+                error,
+            }),
+            Ok(Ok(status)) if !status.success() => Err(LaunchError::EarlyExit {
+                program: program.clone(),
+                code: status.code().unwrap_or(-1),
+                output: log.and_then(|(_, path)| last_output_line(&path)),
+            }),
+            _ => Ok(()),
+        }
+    }
+}
+
+// What a command started with run_captured sends back
+pub enum CapturedOutput {
+    // a line of stdout or stderr, with its newline
+    Text(String),
+    // always the last message, after all the output
+    Exited(io::Result<ExitStatus>),
+}
+
+// A command whose output is shown in the HUD. Unlike launched apps it stays our child, in its
+// own process group so stopping it also stops anything it started.
+pub struct CapturedRun {
+    // cleared once it has exited, before it's reaped, so we never signal a reused pid
+    pid: Arc<Mutex<Option<i32>>>,
+    pub output: async_channel::Receiver<CapturedOutput>,
+}
+
+impl CapturedRun {
+    pub fn stop(&self) {
+        if let Some(pid) = *self.pid.lock().unwrap() {
+            // SAFETY: the pid is still ours until the wait thread reaps it
+            unsafe {
+                libc::kill(-pid, libc::SIGTERM);
+            }
+        }
+    }
+}
+
+// Runs `argv` in the working directory with its output streamed back instead of logged
+pub fn run_captured(argv: &[String]) -> Result<CapturedRun, LaunchError> {
+    let (program, args) = argv.split_first().ok_or(LaunchError::EmptyCommand)?;
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .process_group(0)
+        .spawn()
+        .map_err(|error| LaunchError::Spawn {
+            program: program.clone(),
+            error,
+        })?;
+
+    let (sender, receiver) = async_channel::unbounded();
+    let readers = [
+        child
+            .stdout
+            .take()
+            .map(|o| Box::new(o) as Box<dyn Read + Send>),
+        child
+            .stderr
+            .take()
+            .map(|e| Box::new(e) as Box<dyn Read + Send>),
+    ]
+    .into_iter()
+    .flatten()
+    .map(|pipe| {
+        let sender = sender.clone();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(pipe);
+            let mut line = Vec::new();
+            while reader.read_until(b'\n', &mut line).is_ok_and(|n| n > 0) {
+                let text = String::from_utf8_lossy(&line).to_string();
+                if sender.send_blocking(CapturedOutput::Text(text)).is_err() {
+                    break;
+                }
+                line.clear();
+            }
+        })
+    })
+    .collect::<Vec<_>>();
+
+    let pid = Arc::new(Mutex::new(Some(child.id() as i32)));
+    let waiting_pid = pid.clone();
+    thread::spawn(move || {
+        // wait for it to exit without reaping it, then forget the pid before it can be reused
+        // SAFETY: waitid only writes to info, WNOWAIT leaves the child for wait() below
+        unsafe {
+            let mut info: libc::siginfo_t = std::mem::zeroed();
+            libc::waitid(
+                libc::P_PID,
+                child.id(),
+                &mut info,
+                libc::WEXITED | libc::WNOWAIT,
+            );
+        }
+        *waiting_pid.lock().unwrap() = None;
+        let status = child.wait();
+        // background jobs it started keep the pipes open, their output counts too
+        for reader in readers {
+            let _ = reader.join();
+        }
+        let _ = sender.send_blocking(CapturedOutput::Exited(status));
+    });
+
+    Ok(CapturedRun {
+        pid,
+        output: receiver,
+    })
+}
+
+// Runs in the forked child right before exec. Forking again and exiting the middle process
+// reparents the app to init (or the nearest subreaper), and setsid keeps our signals and
+// controlling terminal away from it. std reports exec failures through a pipe the grandchild
+// inherits, so spawn() still errors if the program can't be run.
+//
+// The middle process hangs around for check_ms first, exiting with the app's status if the
+// app exits in that time, so spawn() + wait() tells us about apps that fail straight away.
+fn detach(check_ms: u32) -> io::Result<()> {
+    // SAFETY: fork, waitpid, usleep, _exit and setsid are all fine to call between fork and exec
+    unsafe {
+        match libc::fork() {
+            -1 => Err(io::Error::last_os_error()),
+            0 => {
+                if libc::setsid() == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                Ok(())
+            }
+            pid => {
+                let mut status = 0;
+                for _ in 0..check_ms / 10 {
+                    if libc::waitpid(pid, &mut status, libc::WNOHANG) == pid {
+                        if libc::WIFEXITED(status) {
+                            libc::_exit(libc::WEXITSTATUS(status));
+                        }
+                        libc::_exit(128 + libc::WTERMSIG(status));
+                    }
+                    libc::usleep(10_000);
+                }
+                libc::_exit(0)
+            }
+        }
+    }
+}
+
+// e.g. app-wlshud-org.mozilla.firefox-18f3a2c4d5e6.scope, the naming other launchers use
+fn scope_unit_name(name: &str) -> String {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_nanos())
+        .unwrap_or(0);
+    format!("app-wlshud-{}-{:x}.scope", sanitize_name(name), nanos)
+}
+
+fn sanitize_name(name: &str) -> String {
+    name.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+// Logs are named <unix time>.<nanoseconds>-<name>.log, only the newest MAX_LAUNCH_LOGS are kept
+fn open_launch_log(argv: &[String], name: &str) -> io::Result<(File, PathBuf)> {
+    let dir = launch_logs_dir();
+    let mut logs = fs::read_dir(&dir)?
+        .flatten()
+        .map(|e| e.path())
+        .filter(|p| p.extension().is_some_and(|ext| ext == "log"))
+        .collect::<Vec<PathBuf>>();
+    // the timestamp prefix makes this oldest first
+    logs.sort();
+    for old in logs.iter().rev().skip(MAX_LAUNCH_LOGS - 1) {
+        let _ = fs::remove_file(old);
+    }
+
+    // down to the nanosecond so launching the same thing twice in a second gets two logs, and
+    // create_new so they can never end up writing into the same one
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    let path = dir.join(format!(
+        "{}.{:09}-{}.log",
+        now.as_secs(),
+        now.subsec_nanos(),
+        sanitize_name(name)
+    ));
+    let mut log = File::create_new(&path)?;
+    writeln!(log, "$ {}", quote_command(argv))?;
+    Ok((log, path))
+}
+
+// The command as it'd be typed into a shell
+pub fn quote_command(argv: &[String]) -> String {
+    argv.iter()
+        .map(|arg| glib::shell_quote(arg).to_string_lossy().to_string())
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn last_output_line(log_path: &Path) -> Option<String> {
+    let contents = fs::read_to_string(log_path).ok()?;
+    // the first line is the command
+    let (_, output) = contents.split_once('\n')?;
+    output
+        .lines()
+        .rfind(|l| !l.trim().is_empty())
+        .map(|l| l.trim().to_owned())
+}
+
+pub fn is_executable_in_path(program: &str) -> bool {
+    if program.contains('/') {
+        return is_executable(Path::new(program));
+    }
+    std::env::var_os("PATH").is_some_and(|paths| {
+        std::env::split_paths(&paths).any(|dir| is_executable(&dir.join(program)))
+    })
+}
+
+// Names of everything in $PATH that can be run and starts with `prefix`, sorted, for Tab
+// completion in the run prompt
+pub fn executables_in_path(prefix: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    let Some(paths) = std::env::var_os("PATH") else {
+        return names;
+    };
+    for dir in std::env::split_paths(&paths) {
+        let Ok(read_dir) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in read_dir.flatten() {
+            if let Ok(name) = entry.file_name().into_string()
+                && name.starts_with(prefix)
+                && is_executable(&entry.path())
+            {
+                names.push(name);
+            }
+        }
+    }
+    names.sort();
+    names.dedup();
+    names
+}
+
+fn is_executable(path: &Path) -> bool {
+    path.metadata()
+        .is_ok_and(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+}
+
+pub fn read_desktop_entry(path: &Path) -> Result<DesktopEntry, LaunchError> {
+    let locales = get_languages_from_env();
+    DesktopEntry::from_path(path, Some(&locales)).map_err(|e| LaunchError::UnreadableEntry {
+        path: path.to_owned(),
+        message: e.to_string(),
+    })
+}
+
+// Splits an Exec= value into arguments and expands its field codes. `files` fill in %f/%F as
+// paths and %u/%U as file:// URIs, without any files those codes are just dropped.
+pub fn expand_exec(
+    exec: &str,
+    entry: &DesktopEntry,
+    files: &[PathBuf],
+) -> Result<Vec<String>, LaunchError> {
+    let bad_exec = |message: &str| LaunchError::BadExec {
+        exec: exec.to_owned(),
+        message: message.to_owned(),
+    };
+
+    let mut argv = Vec::new();
+    // the desktop entry parser has already undone the string escapes, only the quoting is left
+    for (arg, quoted) in split_exec(exec).map_err(bad_exec)? {
+        // field codes aren't allowed in quoted arguments, so those are taken as they are
+        if quoted {
+            argv.push(arg);
+            continue;
+        }
+        match arg.as_str() {
+            "%f" => argv.extend(files.first().map(|f| f.to_string_lossy().to_string())),
+            "%F" => argv.extend(files.iter().map(|f| f.to_string_lossy().to_string())),
+            "%u" => argv.extend(files.first().map(|f| file_uri(f))),
+            "%U" => argv.extend(files.iter().map(|f| file_uri(f))),
+            "%i" => {
+                if let Some(icon) = entry.icon() {
+                    argv.push("--icon".to_owned());
+                    argv.push(icon.to_owned());
+                }
+            }
+            "%c" => argv.push(
+                entry
+                    .name(&get_languages_from_env())
+                    .map(|n| n.to_string())
+                    .unwrap_or_default(),
+            ),
+            "%k" => argv.push(entry.path.to_string_lossy().to_string()),
+            // deprecated, the spec says to remove them
+            "%d" | "%D" | "%n" | "%N" | "%v" | "%m" => {}
+            _ => argv.push(expand_inline_codes(&arg).map_err(bad_exec)?),
+        }
+    }
+
+    if argv.is_empty() {
+        return Err(bad_exec("it's empty"));
+    }
+    Ok(argv)
+}
+
+// Codes in the middle of an argument, only %% makes sense there
+fn expand_inline_codes(arg: &str) -> Result<String, &'static str> {
+    let mut expanded = String::new();
+    let mut chars = arg.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            expanded.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('%') => expanded.push('%'),
+            Some(_) => return Err("field codes have to be arguments of their own"),
+            None => return Err("it ends with a lone %"),
+        }
+    }
+    Ok(expanded)
+}
+
+// Splits on whitespace outside of double quotes, where \" \` \$ and \\ are the only escapes.
+// Each argument comes with whether it was quoted.
+fn split_exec(exec: &str) -> Result<Vec<(String, bool)>, &'static str> {
+    let mut args = Vec::new();
+    let mut chars = exec.chars().peekable();
+    loop {
+        while chars.next_if(|c| c.is_whitespace()).is_some() {}
+        let Some(&first) = chars.peek() else {
+            break;
+        };
+
+        if first == '"' {
+            chars.next();
+            let mut arg = String::new();
+            loop {
+                match chars.next() {
+                    Some('"') => break,
+                    Some('\\') => match chars.next() {
+                        Some(c @ ('"' | '`' | '$' | '\\')) => arg.push(c),
+                        _ => return Err("unknown escape in quotes"),
+                    },
+                    Some(c) => arg.push(c),
+                    None => return Err("unmatched quote"),
+                }
+            }
+            args.push((arg, true));
+        } else {
+            let mut arg = String::new();
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                if c == '"' {
+                    return Err("quotes have to wrap a whole argument");
+                }
+                arg.push(c);
+            }
+            args.push((arg, false));
+        }
+    }
+    Ok(args)
+}
+
+fn file_uri(path: &Path) -> String {
+    gio::File::for_path(path).uri().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ENTRY_PATH: &str = "/usr/share/applications/test.desktop";
+
+    fn entry(icon: Option<&str>) -> DesktopEntry {
+        let mut input = "[Desktop Entry]\nType=Application\nName=Test App\nExec=test\n".to_owned();
+        if let Some(icon) = icon {
+            input.push_str(&format!("Icon={}\n", icon));
+        }
+        DesktopEntry::from_str(ENTRY_PATH, &input, None::<&[&str]>).unwrap()
+    }
+
+    fn expand(exec: &str, files: &[&str]) -> Result<Vec<String>, LaunchError> {
+        let files = files.iter().map(PathBuf::from).collect::<Vec<PathBuf>>();
+        expand_exec(exec, &entry(Some("test-icon")), &files)
+    }
+
+    fn argv(exec: &str, files: &[&str]) -> Vec<String> {
+        expand(exec, files).unwrap_or_else(|e| panic!("{} didn't expand: {}", exec, e))
+    }
+
+    #[test]
+    fn quoted_arguments_keep_spaces_and_unescape() {
+        assert_eq!(
+            argv(r#"prog "two words" "say \"hi\"" "back\\slash""#, &[]),
+            ["prog", "two words", r#"say "hi""#, r"back\slash"]
+        );
+        assert_eq!(
+            argv(r#"prog "\`date\`" "\$HOME""#, &[]),
+            ["prog", "`date`", "$HOME"]
+        );
+        // nothing inside quotes is a field code
+        assert_eq!(
+            argv(r#"prog "%f" "100%""#, &["/tmp/a"]),
+            ["prog", "%f", "100%"]
+        );
+    }
+
+    #[test]
+    fn bad_quoting_is_an_error() {
+        assert!(expand(r#"prog "\n""#, &[]).is_err());
+        assert!(expand(r#"prog "unfinished"#, &[]).is_err());
+        assert!(expand(r#"prog --name="x""#, &[]).is_err());
+    }
+
+    #[test]
+    fn file_codes_without_files_are_dropped() {
+        for code in ["%f", "%F", "%u", "%U"] {
+            assert_eq!(
+                argv(&format!("prog {} --end", code), &[]),
+                ["prog", "--end"]
+            );
+        }
+    }
+
+    #[test]
+    fn file_codes_with_several_files() {
+        let files = ["/tmp/a.txt", "/tmp/b c.txt"];
+        assert_eq!(argv("prog %f", &files), ["prog", "/tmp/a.txt"]);
+        assert_eq!(
+            argv("prog %F", &files),
+            ["prog", "/tmp/a.txt", "/tmp/b c.txt"]
+        );
+        assert_eq!(argv("prog %u", &files), ["prog", "file:///tmp/a.txt"]);
+        assert_eq!(
+            argv("prog %U", &files),
+            ["prog", "file:///tmp/a.txt", "file:///tmp/b%20c.txt"]
+        );
+    }
+
+    #[test]
+    fn percent_signs() {
+        assert_eq!(argv("prog 100%% %%", &[]), ["prog", "100%", "%"]);
+        assert!(expand("prog 50%", &[]).is_err());
+        assert!(expand("prog %", &[]).is_err());
+        assert!(expand("prog --file=%f", &[]).is_err());
+    }
+
+    #[test]
+    fn deprecated_codes_are_dropped() {
+        assert_eq!(argv("prog %d %D %n %N %v %m end", &[]), ["prog", "end"]);
+    }
+
+    #[test]
+    fn entry_codes() {
+        assert_eq!(argv("prog %c", &[]), ["prog", "Test App"]);
+        assert_eq!(argv("prog %i", &[]), ["prog", "--icon", "test-icon"]);
+        assert_eq!(argv("prog %k", &[]), ["prog", ENTRY_PATH]);
+        // no icon, no --icon
+        assert_eq!(expand_exec("prog %i", &entry(None), &[]).unwrap(), ["prog"]);
+    }
+
+    #[test]
+    fn empty_exec_is_an_error() {
+        assert!(expand("", &[]).is_err());
+        assert!(expand("%f", &[]).is_err());
+    }
+}
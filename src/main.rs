@@ -1,73 +1,104 @@
-use std::{fs, process::Command};
+use std::{
+    fs,
+    path::Path,
+    rc::Rc,
+    sync::{Arc, Mutex},
+};
 
 use gtk4::{
-    ApplicationWindow, Box, CssProvider, EventControllerKey, IconTheme, Image, ListBox, Overlay,
+    ApplicationWindow, Box, CssProvider, EventControllerKey, IconTheme, Image, Overlay,
     ScrolledWindow, SearchEntry,
     gdk::{Display, Key},
     gio::{
+        ApplicationFlags, Cancellable, File, FileMonitor, FileMonitorEvent, FileMonitorFlags,
         SimpleActionGroup,
-        prelude::{ActionMapExtManual, ApplicationExt, ApplicationExtManual},
+        prelude::{
+            ActionMapExtManual, ApplicationExt, ApplicationExtManual, FileExt, FileMonitorExt,
+        },
         resources_register_include,
     },
-    glib::{clone, object::CastNone},
-    prelude::{BoxExt, GtkApplicationExt, GtkWindowExt, WidgetExt},
+    glib::{clone, variant::ToVariant},
+    prelude::{AdjustmentExt, BoxExt, GtkApplicationExt, GtkWindowExt, WidgetExt},
 };
 use gtk4::{glib, prelude::EditableExt};
 use gtk4_layer_shell::LayerShell;
 use libadwaita::{
-    Application, CallbackAnimationTarget, Easing, TimedAnimation, prelude::AnimationExt,
+    Application, CallbackAnimationTarget, Easing, TimedAnimation, ToastOverlay,
+    prelude::AnimationExt,
 };
 
 use crate::{
-    actions::build_actions,
-    config::{ConfigData, css_file_path},
+    actions::{build_actions, show_error},
+    cli::{add_cli_options, check_local_options, handle_command_line},
+    config::{ConfigData, css_file_path, shortcuts_file_path},
+    dbus::register_control_interface,
+    dmenu::{dmenu_prompt_from_args, run_dmenu},
+    history::{LaunchHistory, RunHistory},
+    launcher::{Launcher, executables_in_path},
+    providers::{FILES_PREFIXES, RUN_PREFIX, files_query_path, is_path_prefix},
     searching::get_file_search_entries,
     shortcuts::ShortcutsDisplay,
 };
 use crate::{
     main_widgets::build_main_widgets,
-    searching::{SearchDatabase, build_search_results},
+    searching::{
+        SearchDatabase, SearchWorker, add_result_keybinds, build_search_results,
+        selected_result_row, update_search_results,
+    },
 };
 
 mod actions;
+mod calculator;
+mod cli;
 mod config;
+mod dbus;
+mod dmenu;
+mod history;
+mod launcher;
 mod main_widgets;
+mod providers;
 mod searching;
 mod shortcuts;
+mod units;
 
-const APP_MARGIN: i32 = 32;
-const APP_ID: &str = "com.DrewCodesBadly.wlshud";
+pub const APP_ID: &str = "com.DrewCodesBadly.wlshud";
 const DEFAULT_CSS_STRING: &str = include_str!("nonrust/default_style.css");
 
 fn main() -> glib::ExitCode {
     let _ = resources_register_include!("wlshud.gresource");
+    // dmenu mode needs its own stdin and stdout, so it never goes through the running instance
+    if let Some(prompt) = dmenu_prompt_from_args(std::env::args().skip(1)) {
+        return run_dmenu(prompt);
+    }
     let app = libadwaita::Application::builder()
         .application_id(APP_ID)
+        .flags(ApplicationFlags::HANDLES_COMMAND_LINE)
         .build();
+    add_cli_options(&app);
     // startup tasks
-    app.connect_startup(|_| {
-        let provider = CssProvider::new();
-
-        // Handle loading CSS
-        let path = css_file_path();
-        if let Ok(css) = fs::read_to_string(&path) {
-            provider.load_from_data(&css);
-        } else {
-            let _ = fs::write(path, DEFAULT_CSS_STRING);
-            provider.load_from_data(DEFAULT_CSS_STRING);
-        }
+    app.connect_startup(|app| {
+        let provider = load_style();
 
-        // load icons
-        let display = Display::default().expect("No display connected.");
-        let theme = IconTheme::for_display(&display);
-        gtk4::style_context_add_provider_for_display(
-            &display,
-            &provider,
-            gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION,
+        let reload_css = clone!(
+            #[strong]
+            provider,
+            move || {
+                if let Ok(css) = fs::read_to_string(css_file_path()) {
+                    provider.load_from_data(&css);
+                }
+            }
         );
-        theme.add_resource_path("/wlshud/icons");
+        // Reapply the CSS whenever style.css changes so themes can be edited live
+        if let Some(monitor) = watch_config_file(&css_file_path(), reload_css.clone()) {
+            app.connect_shutdown(move |_| {
+                monitor.cancel();
+            });
+        }
+        register_control_interface(app, reload_css);
     });
     app.connect_activate(activate);
+    app.connect_handle_local_options(check_local_options);
+    app.connect_command_line(handle_command_line);
 
     // Set binds
     app.set_accels_for_action("wlshud.close", &["Escape"]);
@@ -78,20 +109,29 @@ fn main() -> glib::ExitCode {
 }
 
 fn activate(app: &Application) {
-    let search_database = SearchDatabase::new();
+    // In daemon mode the window from last time is still around, so just show it again
+    if let Some(window) = app.windows().first() {
+        let _ = window.activate_action("wlshud.show", None);
+        return;
+    }
+
+    let window = build_window(app);
+    // show the window and play the starting animation
+    let _ = <ApplicationWindow as WidgetExt>::activate_action(&window, "wlshud.show", None);
+}
+
+// Builds the HUD from what's in the config files right now, without showing it
+pub fn build_window(app: &Application) -> ApplicationWindow {
     let config = ConfigData::default();
-    let shortcuts_display = ShortcutsDisplay::new(config.shortcuts_list());
+    let settings = config.settings();
+    let launch_history = Arc::new(Mutex::new(LaunchHistory::load()));
+    let run_history = Arc::new(Mutex::new(RunHistory::load()));
+    let search_database =
+        SearchDatabase::new(settings, launch_history.clone(), run_history.clone());
+    let shortcuts_display = Rc::new(ShortcutsDisplay::new(config.shortcuts_list()));
 
     let window = gtk4::ApplicationWindow::new(app);
-
-    window.init_layer_shell();
-    window.set_layer(gtk4_layer_shell::Layer::Overlay);
-    window.set_keyboard_mode(gtk4_layer_shell::KeyboardMode::Exclusive);
-    // anchors to all 4 sides (take up whole screen)
-    window.set_anchor(gtk4_layer_shell::Edge::Bottom, true);
-    window.set_anchor(gtk4_layer_shell::Edge::Top, true);
-    window.set_anchor(gtk4_layer_shell::Edge::Left, true);
-    window.set_anchor(gtk4_layer_shell::Edge::Right, true);
+    init_overlay_window(&window);
 
     let entry = SearchEntry::builder()
         .hexpand(true)
@@ -101,29 +141,66 @@ fn activate(app: &Application) {
 
     let outer_box = Box::builder()
         .orientation(gtk4::Orientation::Vertical)
-        .margin_bottom(APP_MARGIN)
-        .margin_top(APP_MARGIN)
-        .margin_end(APP_MARGIN)
-        .margin_start(APP_MARGIN)
+        .margin_bottom(settings.app_margin)
+        .margin_top(settings.app_margin)
+        .margin_end(settings.app_margin)
+        .margin_start(settings.app_margin)
         .build();
     outer_box.append(&entry);
     let dialog_overlay = Overlay::builder().child(&outer_box).build();
+    let toast_overlay = ToastOverlay::new();
+    toast_overlay.set_child(Some(&dialog_overlay));
+
+    // Rebuild the shortcuts whenever shortcuts.json changes, from the dialogs or an editor
+    if let Some(monitor) = watch_config_file(
+        &shortcuts_file_path(),
+        clone!(
+            #[weak]
+            window,
+            move || {
+                let _ = <ApplicationWindow as WidgetExt>::activate_action(
+                    &window,
+                    "wlshud.reload-config",
+                    None,
+                );
+            }
+        ),
+    ) {
+        window.connect_destroy(move |_| {
+            monitor.cancel();
+        });
+    }
     let search_results_window = ScrolledWindow::builder().vexpand(true).build();
 
-    let default_box = build_main_widgets(&shortcuts_display);
+    let default_box = build_main_widgets(&shortcuts_display, settings);
     outer_box.append(&default_box);
 
+    // both can be remapped or turned off in settings.json
+    let run_prefix = settings
+        .search_prefix("run", RUN_PREFIX)
+        .filter(|p| !p.is_empty());
+    let files_prefixes = settings
+        .search_prefixes_for("files", &FILES_PREFIXES)
+        .unwrap_or_default();
     // TODO: check this, figure out how it works
     // Send key presses to the shortcuts display to trigger shortcuts.
     let key_controller = EventControllerKey::builder().build();
     key_controller.connect_key_pressed(clone!(
         #[strong]
         entry,
+        #[strong]
+        shortcuts_display,
+        #[strong]
+        files_prefixes,
         move |_, key, _, _| {
             // Do not handle events if the search entry currently has focus.
             let t = entry.text();
-            if key == Key::Tab && (t.starts_with('/') || t.starts_with('~')) {
-                let entries = get_file_search_entries(&t);
+            let files_prefix = longest_files_prefix(&files_prefixes, &t);
+            if key == Key::Tab
+                && let Some(prefix) = files_prefix
+            {
+                let entries =
+                    get_file_search_entries(&files_query_path(prefix, &t[prefix.len()..]));
                 if let Some(e) = entries.first() {
                     let mut new_t = e
                         .location
@@ -133,12 +210,40 @@ fn activate(app: &Application) {
                     if !e.location.is_file() {
                         new_t.push('/');
                     }
+                    // the completed path is absolute, other prefixes still have to go in front
+                    if !is_path_prefix(prefix) {
+                        new_t.insert_str(0, prefix);
+                    }
                     entry.set_text(&new_t);
-                    entry.set_position(new_t.len() as i32);
+                    entry.set_position(new_t.chars().count() as i32);
                     glib::Propagation::Stop
                 } else {
                     glib::Propagation::Proceed
                 }
+            } else if key == Key::Tab
+                && let Some(run_prefix) = &run_prefix
+                && let Some(program) = t.strip_prefix(run_prefix.as_str()).map(str::trim_start)
+                && !program.is_empty()
+                && !program.contains(char::is_whitespace)
+            {
+                // complete the program name from $PATH, as far as all the matches agree
+                let names = executables_in_path(program);
+                let Some(first) = names.first() else {
+                    return glib::Propagation::Proceed;
+                };
+                let mut completed = first.clone();
+                for name in &names[1..] {
+                    while !name.starts_with(completed.as_str()) {
+                        completed.pop();
+                    }
+                }
+                if names.len() == 1 {
+                    completed.push(' ');
+                }
+                let new_t = format!("{}{}", run_prefix, completed);
+                entry.set_text(&new_t);
+                entry.set_position(new_t.chars().count() as i32);
+                glib::Propagation::Stop
             } else if entry.has_focus() {
                 glib::Propagation::Proceed
             } else if let Some(char) = key.to_unicode() {
@@ -168,18 +273,93 @@ fn activate(app: &Application) {
         .widget(&window)
         .target(&opacity_target)
         .easing(Easing::EaseOutCirc)
-        .duration(250)
+        .duration(settings.fade_duration.as_millis() as u32)
         .build();
 
     // Actions
     let actions = SimpleActionGroup::new();
-    actions.add_action_entries(build_actions(&window, &start_fade, &dialog_overlay));
+    actions.add_action_entries(build_actions(
+        &window,
+        &start_fade,
+        &dialog_overlay,
+        &toast_overlay,
+        &entry,
+        &shortcuts_display,
+        &launch_history,
+        &run_history,
+        &Rc::new(Launcher::new(settings)),
+        settings,
+    ));
     window.insert_action_group("wlshud", Some(&actions));
 
+    // The close action plays the fade in reverse, finish closing once it's done
+    let daemon_mode = settings.daemon_mode;
+    start_fade.connect_done(clone!(
+        #[weak]
+        window,
+        #[weak]
+        entry,
+        #[weak]
+        outer_box,
+        #[weak]
+        dialog_overlay,
+        #[strong]
+        default_box,
+        #[strong]
+        shortcuts_display,
+        move |fade| {
+            if !fade.is_reverse() {
+                return;
+            }
+            if !daemon_mode {
+                window.close();
+                return;
+            }
+
+            // Daemon mode keeps the window around, so put it back to how it started
+            window.set_visible(false);
+            entry.set_text("");
+            if let Some(last_child) = outer_box.last_child() {
+                outer_box.remove(&last_child);
+            }
+            outer_box.append(&default_box);
+            shortcuts_display.reset();
+            // remove any dialogs left open
+            let mut child = dialog_overlay.first_child();
+            while let Some(c) = child {
+                child = c.next_sibling();
+                if Some(&c) != dialog_overlay.child().as_ref() {
+                    dialog_overlay.remove_overlay(&c);
+                }
+            }
+        }
+    ));
+
+    // Searches run on their own thread and the results get filled in as they arrive
+    let (search_worker, search_updates) = SearchWorker::new(search_database);
+    let results_list = build_search_results(Vec::new());
+    search_results_window.set_child(Some(&results_list));
+    glib::spawn_future_local(clone!(
+        #[weak]
+        results_list,
+        #[weak]
+        search_results_window,
+        async move {
+            let mut shown_generation = 0;
+            while let Some(update) = search_updates.next().await {
+                // the first batch for a new query replaces the old results
+                let append = update.generation == shown_generation;
+                shown_generation = update.generation;
+                update_search_results(&results_list, update.results, append);
+                if !append {
+                    search_results_window.vadjustment().set_value(0.0);
+                }
+            }
+        }
+    ));
+
     // Connect search bar to input handling
     entry.connect_text_notify(clone!(
-        #[strong]
-        search_database,
         #[strong]
         search_results_window,
         #[strong]
@@ -188,6 +368,7 @@ fn activate(app: &Application) {
         outer_box,
         move |entry| {
             if entry.text().is_empty() {
+                search_worker.cancel();
                 // the has focus check here is to make sure this doesn't rebuild and
                 // break the focus when space is pressed to trigger shortcuts
                 if entry.focus_child().is_some() {
@@ -201,18 +382,20 @@ fn activate(app: &Application) {
                 entry.set_text("");
                 default_box.grab_focus();
             } else {
-                let results = search_database.search(&entry.text());
-                let results_display = build_search_results(results);
-                // should always be true
-                if let Some(last_child) = outer_box.last_child() {
-                    outer_box.remove(&last_child);
+                search_worker.search(&entry.text());
+                if search_results_window.parent().is_none() {
+                    // should always be true
+                    if let Some(last_child) = outer_box.last_child() {
+                        outer_box.remove(&last_child);
+                    }
+                    outer_box.append(&search_results_window);
                 }
-                search_results_window.set_child(Some(&results_display));
-                outer_box.append(&search_results_window);
             }
         }
     ));
 
+    add_result_keybinds(&entry, &search_results_window);
+
     // `activate` means when the user presses enter
     entry.connect_activate(clone!(
         #[weak]
@@ -221,17 +404,14 @@ fn activate(app: &Application) {
             let t = entry.text();
             // hacky workaround to open directories when you have the ending /
             // since it makes tab autocomplete work as expected and kinda annoyed me
-            if t.ends_with('/') && (t.starts_with('/') || t.starts_with('~')) {
-                let _ = Command::new("xdg-open").arg(t).spawn();
-                let _ = entry.activate_action("wlshud.close", None);
-            } else if let Some(c) = search_results_window.child() {
-                // there's a GtkViewport in between these for some reason
-                c.first_child()
-                    .and_downcast::<ListBox>()
-                    .and_then(|b| b.row_at_index(0))
-                    .inspect(|r| {
-                        r.activate();
-                    });
+            if t.ends_with('/')
+                && let Some(prefix) = longest_files_prefix(&files_prefixes, &t)
+            {
+                let path = files_query_path(prefix, &t[prefix.len()..]);
+                let exec = vec!["xdg-open".to_owned(), path];
+                let _ = entry.activate_action("wlshud.exec", Some(&exec.to_variant()));
+            } else if let Some(row) = selected_result_row(&search_results_window) {
+                row.activate();
             }
         }
     ));
@@ -242,11 +422,79 @@ fn activate(app: &Application) {
             let _ = <ApplicationWindow as WidgetExt>::activate_action(window, "wlshud.close", None);
         }
     });
-    window.set_child(Some(&dialog_overlay));
-    window.show();
+    window.set_child(Some(&toast_overlay));
+
+    // let the user know why shortcuts or settings might be missing
+    for error in config
+        .shortcuts_errors()
+        .iter()
+        .chain(config.settings_errors())
+    {
+        show_error(&toast_overlay, &error.to_string());
+    }
+    window
+}
 
-    // play starting animation
-    start_fade.play();
+// The files prefix a query starts with, the longest one if several match
+fn longest_files_prefix<'a>(prefixes: &'a [String], query: &str) -> Option<&'a String> {
+    prefixes
+        .iter()
+        .filter(|p| !p.is_empty() && query.starts_with(p.as_str()))
+        .max_by_key(|p| p.len())
+}
+
+// Loads style.css (writing out the default if there isn't one) and the bundled icons
+pub fn load_style() -> CssProvider {
+    let provider = CssProvider::new();
+
+    // Handle loading CSS
+    let path = css_file_path();
+    if let Ok(css) = fs::read_to_string(&path) {
+        provider.load_from_data(&css);
+    } else {
+        let _ = fs::write(&path, DEFAULT_CSS_STRING);
+        provider.load_from_data(DEFAULT_CSS_STRING);
+    }
+
+    // load icons
+    let display = Display::default().expect("No display connected.");
+    let theme = IconTheme::for_display(&display);
+    gtk4::style_context_add_provider_for_display(
+        &display,
+        &provider,
+        gtk4::STYLE_PROVIDER_PRIORITY_APPLICATION,
+    );
+    theme.add_resource_path("/wlshud/icons");
+
+    provider
+}
+
+// Makes the window a fullscreen layer shell overlay that takes all keyboard input
+pub fn init_overlay_window(window: &ApplicationWindow) {
+    window.init_layer_shell();
+    window.set_layer(gtk4_layer_shell::Layer::Overlay);
+    window.set_keyboard_mode(gtk4_layer_shell::KeyboardMode::Exclusive);
+    // anchors to all 4 sides (take up whole screen)
+    window.set_anchor(gtk4_layer_shell::Edge::Bottom, true);
+    window.set_anchor(gtk4_layer_shell::Edge::Top, true);
+    window.set_anchor(gtk4_layer_shell::Edge::Left, true);
+    window.set_anchor(gtk4_layer_shell::Edge::Right, true);
+}
+
+// Calls on_change whenever the file is written, including when it gets replaced by a rename
+fn watch_config_file(path: &Path, on_change: impl Fn() + 'static) -> Option<FileMonitor> {
+    let monitor = File::for_path(path)
+        .monitor_file(FileMonitorFlags::NONE, None::<&Cancellable>)
+        .ok()?;
+    monitor.connect_changed(move |_, _, _, event| {
+        if matches!(
+            event,
+            FileMonitorEvent::ChangesDoneHint | FileMonitorEvent::Created
+        ) {
+            on_change();
+        }
+    });
+    Some(monitor)
 }
 
 pub fn icon_from_name(icon_name: &str) -> Image {
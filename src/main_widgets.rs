@@ -1,19 +1,28 @@
-use std::{collections::HashMap, fs, process::Command, time::Duration};
+use std::{collections::HashMap, ffi::OsStr, fs, time::Duration};
 
 use gtk4::{
-    Box, Button, Frame, Image, Label, ScrolledWindow, TextView, Widget,
-    glib::{self, clone, object::IsA, spawn_future_local},
+    Box, Button, Frame, Image, Label, ScrolledWindow, TextView, Widget, gio,
+    glib::{
+        self, clone,
+        object::{Cast, IsA},
+        spawn_future_local,
+    },
     prelude::{BoxExt, ButtonExt, TextBufferExt, TextViewExt, WidgetExt},
 };
 
-use crate::{config::notes_file_path, shortcuts::ShortcutsDisplay};
+use crate::{
+    actions::show_error,
+    config::{NOTES_BACKUP_INTERVAL, Settings, notes_file_path, write_config_file},
+    shortcuts::ShortcutsDisplay,
+};
 
-// currently set to 250 as that is the length of the close animation
-const NOTES_BOX_SAVE_DELAY: Duration = Duration::from_millis(250);
 // this system SUCKS what the hell gtk4. i find it hard to believe there isn't a better way to do this
 const MAX_LABEL_SIZE: i32 = 24;
 
-pub fn build_main_widgets(shortcuts_display: &ShortcutsDisplay) -> impl IsA<Widget> {
+pub fn build_main_widgets(
+    shortcuts_display: &ShortcutsDisplay,
+    settings: &Settings,
+) -> impl IsA<Widget> {
     let outer_box = Box::builder()
         .orientation(gtk4::Orientation::Horizontal)
         // .spacing(16)
@@ -62,12 +71,19 @@ pub fn build_main_widgets(shortcuts_display: &ShortcutsDisplay) -> impl IsA<Widg
         .height_request(48)
         .action_name("wlshud.remove-shortcuts")
         .build();
+    let restore_backup_button = Button::builder()
+        .icon_name("history-undo-symbolic")
+        .width_request(48)
+        .height_request(48)
+        .action_name("wlshud.restore-backups")
+        .build();
     left_bar_box.append(&new_shortcut_button);
     left_bar_box.append(&delete_shortcut_button);
+    left_bar_box.append(&restore_backup_button);
 
-    let media_box = build_media_box();
+    let media_box = build_media_box(settings.media_poll_interval);
 
-    let notes_view = build_notes_box();
+    let notes_view = build_notes_box(settings.notes_save_delay);
     let notes_box = Frame::builder().child(&notes_view).build();
 
     // populate two inner rows
@@ -82,12 +98,26 @@ pub fn build_main_widgets(shortcuts_display: &ShortcutsDisplay) -> impl IsA<Widg
     outer_box
 }
 
-fn build_notes_box() -> impl IsA<Widget> {
+fn build_notes_box(save_delay: Duration) -> impl IsA<Widget> {
     let notes_box = TextView::builder().vexpand(true).build();
     let buffer = notes_box.buffer();
     if let Ok(s) = fs::read_to_string(notes_file_path()) {
         buffer.set_text(&s);
     }
+    // In daemon mode the window only gets hidden, so whatever changed the file in the meantime
+    // (like restoring a backup) has to be picked up when it's shown again, or the next save
+    // would write the old text back over it
+    notes_box.connect_map(clone!(
+        #[weak]
+        buffer,
+        move |_| {
+            if let Ok(s) = fs::read_to_string(notes_file_path())
+                && s != buffer.text(&buffer.start_iter(), &buffer.end_iter(), true)
+            {
+                buffer.set_text(&s);
+            }
+        }
+    ));
     let notes_box_scroll_container = ScrolledWindow::builder()
         .width_request(400)
         .vexpand(true)
@@ -104,12 +134,17 @@ fn build_notes_box() -> impl IsA<Widget> {
     spawn_future_local(clone!(
         #[weak]
         buffer,
+        #[weak]
+        notes_box,
         async move {
             while let Ok(()) = receiver.recv().await {
-                glib::timeout_future(NOTES_BOX_SAVE_DELAY).await;
+                glib::timeout_future(save_delay).await;
                 if receiver.is_empty() {
                     let s = buffer.text(&buffer.start_iter(), &buffer.end_iter(), true);
-                    let _ = fs::write(notes_file_path(), s);
+                    if let Err(e) = write_config_file(&notes_file_path(), &s, NOTES_BACKUP_INTERVAL)
+                    {
+                        show_error(&notes_box, &format!("Could not save notes: {}", e));
+                    }
                 }
             }
         }
@@ -118,7 +153,7 @@ fn build_notes_box() -> impl IsA<Widget> {
     notes_box_scroll_container
 }
 
-fn build_media_box() -> impl IsA<Widget> {
+fn build_media_box(poll_interval: Duration) -> impl IsA<Widget> {
     let img = Image::builder()
         .valign(gtk4::Align::Center)
         .icon_name("speaker-0-symbolic")
@@ -196,72 +231,10 @@ fn build_media_box() -> impl IsA<Widget> {
         artist_label,
         async move {
             loop {
-                let playing_result = Command::new("playerctl").arg("status").output();
-                if let Ok(playing_res) = playing_result {
-                    let string_output = String::from_utf8(playing_res.stdout);
-                    match string_output {
-                        Ok(s) => {
-                            // "Playing" or "Paused"
-                            // Lately it's started saying "Paused" while no players run
-                            // so this logic needed to be edited a bit.
-                            let metadata = fetch_playerctl_metadata();
-                            if s.starts_with("P") && !metadata.is_empty() {
-                                let t = metadata.get("title");
-                                title_label.set_text(t.unwrap_or(&"Untitled".to_owned()));
-                                title_label
-                                    .set_tooltip_text(Some(t.unwrap_or(&"Untitled".to_owned())));
-                                if let Some(album) = metadata.get("album").filter(|s| !s.is_empty())
-                                {
-                                    artist_label.set_text(&format!(
-                                        "{} - {}",
-                                        metadata
-                                            .get("artist")
-                                            .unwrap_or(&"Unknown Artist".to_owned()),
-                                        album
-                                    ));
-                                } else {
-                                    artist_label.set_text(
-                                        metadata
-                                            .get("artist")
-                                            .unwrap_or(&"Unknown Artist".to_owned()),
-                                    );
-                                }
-                                // gtk won't handle URLs so we do this instead. works with firefox media player so hey
-                                if let Some(url) = metadata.get("artUrl")
-                                    && let Some(stripped_url) = url.strip_prefix("file://")
-                                {
-                                    img.set_from_file(Some(stripped_url));
-                                } else {
-                                    img.set_icon_name(Some("music-note-single-symbolic"));
-                                }
-
-                                // Finally handle playing or paused
-                                // 2nd == 'l' --> means the word is "Playing" not "Paused"
-                                if s.chars().nth(1).filter(|c| *c == 'l').is_some() {
-                                    play_pause_btn.set_icon_name("pause-symbolic");
-                                } else {
-                                    play_pause_btn.set_icon_name("play-symbolic");
-                                }
-                            } else {
-                                title_label.set_text("No media playing");
-                                artist_label.set_text("");
-                                img.set_icon_name(Some("speaker-0-symbolic"));
-                            }
-                        }
-                        Err(e) => {
-                            title_label.set_text("Error fetching media players");
-                            artist_label.set_text(&e.to_string());
-                            img.set_icon_name(Some("cross-large-symbolic"));
-                        }
-                    }
-                } else {
-                    title_label.set_text("Cannot run playerctl");
-                    artist_label.set_text("please install playerctl to display media!");
-                    img.set_icon_name(Some("cross-large-symbolic"));
-                }
+                update_media_box(&img, &play_pause_btn, &title_label, &artist_label).await;
 
-                // Run this loop once per second
-                glib::timeout_future(Duration::from_secs(1)).await;
+                // Run this loop once per poll interval (1 second by default)
+                glib::timeout_future(poll_interval).await;
             }
         }
     ));
@@ -274,28 +247,137 @@ fn build_media_box() -> impl IsA<Widget> {
             .starts_with("pl")
         {
             btn.set_icon_name("pause-symbolic");
-            let _ = Command::new("playerctl").arg("play").spawn();
+            run_playerctl(btn, "play");
         } else {
             btn.set_icon_name("play-symbolic");
-            let _ = Command::new("playerctl").arg("pause").spawn();
+            run_playerctl(btn, "pause");
         }
     });
-    skip_bck_btn.connect_clicked(|_| {
-        let _ = Command::new("playerctl").arg("previous").spawn();
+    skip_bck_btn.connect_clicked(|btn| {
+        run_playerctl(btn, "previous");
     });
-    skip_fwd_btn.connect_clicked(|_| {
-        let _ = Command::new("playerctl").arg("next").spawn();
+    skip_fwd_btn.connect_clicked(|btn| {
+        run_playerctl(btn, "next");
     });
 
     frame
 }
 
-fn fetch_playerctl_metadata() -> HashMap<String, String> {
+// Fills in the media box from what playerctl says is playing
+async fn update_media_box(
+    img: &Image,
+    play_pause_btn: &Button,
+    title_label: &Label,
+    artist_label: &Label,
+) {
+    let Ok(process) = spawn_playerctl("status") else {
+        title_label.set_text("Cannot run playerctl");
+        artist_label.set_text("please install playerctl to display media!");
+        img.set_icon_name(Some("cross-large-symbolic"));
+        return;
+    };
+    match process.communicate_utf8_future(None).await {
+        Ok((stdout, _)) => {
+            let s = stdout.unwrap_or_default();
+            // "Playing" or "Paused"
+            // Lately it's started saying "Paused" while no players run
+            // so this logic needed to be edited a bit.
+            let metadata = fetch_playerctl_metadata().await;
+            if s.starts_with("P") && !metadata.is_empty() {
+                let t = metadata.get("title");
+                title_label.set_text(t.unwrap_or(&"Untitled".to_owned()));
+                title_label.set_tooltip_text(Some(t.unwrap_or(&"Untitled".to_owned())));
+                if let Some(album) = metadata.get("album").filter(|s| !s.is_empty()) {
+                    artist_label.set_text(&format!(
+                        "{} - {}",
+                        metadata
+                            .get("artist")
+                            .unwrap_or(&"Unknown Artist".to_owned()),
+                        album
+                    ));
+                } else {
+                    artist_label.set_text(
+                        metadata
+                            .get("artist")
+                            .unwrap_or(&"Unknown Artist".to_owned()),
+                    );
+                }
+                // gtk won't handle URLs so we do this instead. works with firefox media player so hey
+                if let Some(url) = metadata.get("artUrl")
+                    && let Some(stripped_url) = url.strip_prefix("file://")
+                {
+                    img.set_from_file(Some(stripped_url));
+                } else {
+                    img.set_icon_name(Some("music-note-single-symbolic"));
+                }
+
+                // Finally handle playing or paused
+                // 2nd == 'l' --> means the word is "Playing" not "Paused"
+                if s.chars().nth(1).filter(|c| *c == 'l').is_some() {
+                    play_pause_btn.set_icon_name("pause-symbolic");
+                } else {
+                    play_pause_btn.set_icon_name("play-symbolic");
+                }
+            } else {
+                title_label.set_text("No media playing");
+                artist_label.set_text("");
+                img.set_icon_name(Some("speaker-0-symbolic"));
+            }
+        }
+        Err(e) => {
+            title_label.set_text("Error fetching media players");
+            artist_label.set_text(&e.to_string());
+            img.set_icon_name(Some("cross-large-symbolic"));
+        }
+    }
+}
+
+// playerctl is run through gio so waiting on it never blocks the UI, a player that's stuck
+// answering would otherwise freeze the whole HUD
+fn spawn_playerctl(command: &str) -> Result<gio::Subprocess, glib::Error> {
+    gio::Subprocess::newv(
+        &[OsStr::new("playerctl"), OsStr::new(command)],
+        gio::SubprocessFlags::STDOUT_PIPE | gio::SubprocessFlags::STDERR_PIPE,
+    )
+}
+
+// Shows why if it fails, which is usually no players running or playerctl not being installed
+fn run_playerctl(widget: &impl IsA<Widget>, command: &str) {
+    let command = command.to_owned();
+    spawn_future_local(clone!(
+        #[weak(rename_to = widget)]
+        widget.upcast_ref::<Widget>(),
+        async move {
+            let process = match spawn_playerctl(&command) {
+                Ok(process) => process,
+                Err(e) => {
+                    show_error(&widget, &format!("Could not run playerctl: {}", e));
+                    return;
+                }
+            };
+            if let Ok((_, stderr)) = process.communicate_utf8_future(None).await
+                && !process.is_successful()
+            {
+                show_error(
+                    &widget,
+                    &format!(
+                        "playerctl {} failed: {}",
+                        command,
+                        stderr.as_deref().unwrap_or_default().trim()
+                    ),
+                );
+            }
+        }
+    ));
+}
+
+async fn fetch_playerctl_metadata() -> HashMap<String, String> {
     let mut map = HashMap::new();
-    let metadata_result = Command::new("playerctl").arg("metadata").output();
-    if let Ok(output) = metadata_result
-        && let Ok(s) = String::from_utf8(output.stdout)
-    {
+    let metadata_result = match spawn_playerctl("metadata") {
+        Ok(process) => process.communicate_utf8_future(None).await.ok(),
+        Err(_) => None,
+    };
+    if let Some((Some(s), _)) = metadata_result {
         for (k, v) in s.lines().map(|l| {
             let start_cut = &l[l.find(':').map(|i| i + 1).unwrap_or(0)..];
             // if this 'find' fails the output is just bad :/
@@ -0,0 +1,777 @@
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, BufRead, BufReader, Write},
+    os::unix::process::CommandExt,
+    path::Path,
+    process::{Command, Stdio},
+    sync::{
+        Arc, Mutex,
+        mpsc::{self, RecvTimeoutError},
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use freedesktop_desktop_entry::{current_desktop, desktop_entries, get_languages_from_env};
+use gtk4::glib::{Variant, variant::ToVariant};
+use rust_fuzzy_search::fuzzy_compare;
+
+use crate::{
+    calculator::{evaluate, format_number},
+    config::{ScriptProviderConfig, Settings, parse_command_line},
+    history::{LaunchHistory, RunHistory, RunHistoryEntry},
+    launcher::{RunMode, expand_exec},
+    searching::{
+        ResultKind, SearchHandle, SearchResult, SearchResults, file_search_entries,
+        match_positions, should_show_entry,
+    },
+    units::convert,
+};
+
+// names of the built in providers, as used in the search_prefixes setting
+pub const BUILT_IN_PROVIDERS: [&str; 5] = ["apps", "files", "run", "calculator", "units"];
+// what queries for each built in provider start with, unless settings.json says otherwise
+pub const FILES_PREFIXES: [&str; 2] = ["/", "~"];
+pub const RUN_PREFIX: &str = ">";
+pub const APPS_PREFIX: &str = "";
+// for the calculator and unit conversions: anything that works out gets an answer, = is for when
+// that's all that's wanted
+pub const CALCULATION_PREFIXES: [&str; 2] = ["=", ""];
+
+// how well an old command has to match to show up under the run prompt
+const MIN_RUN_HISTORY_SCORE: f32 = 0.2;
+// how often a script provider that hasn't printed anything gets checked on
+const SCRIPT_POLL_INTERVAL: Duration = Duration::from_millis(20);
+
+// A source of search results. SearchDatabase picks which ones get a query by its prefix, see
+// SearchDatabase::search.
+pub trait SearchProvider: Send + Sync {
+    // what it's called in the search_prefixes setting
+    fn name(&self) -> &str;
+
+    // What queries for it start with when settings.json doesn't say, an empty prefix gets every
+    // query no other prefix claims
+    fn default_prefixes(&self) -> Vec<String>;
+
+    // Whether the prefix gets cut off the query before it's passed to search. File paths need
+    // theirs kept.
+    fn strips_prefix(&self) -> bool {
+        true
+    }
+
+    // Sends what matches `query` through `handle`, best first. It can be sent in more than one
+    // batch when some results take longer to find than others.
+    fn search(&self, query: &str, handle: &SearchHandle);
+
+    // The window action picking one of its results runs, with its target. None runs the
+    // result's execute_command with wlshud.exec.
+    fn activate(&self, _result: &SearchResult) -> Option<(String, Option<Variant>)> {
+        None
+    }
+}
+
+// Desktop entry fields that app searches match against, and how much a match in each counts
+#[derive(Clone, Copy, PartialEq)]
+enum AppField {
+    Name,
+    GenericName,
+    Keywords,
+    Categories,
+    Comment,
+}
+
+impl AppField {
+    fn weight(&self) -> f32 {
+        match self {
+            AppField::Name => 1.0,
+            AppField::GenericName => 0.8,
+            AppField::Keywords => 0.7,
+            AppField::Categories => 0.5,
+            AppField::Comment => 0.4,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            AppField::Name => "Name",
+            AppField::GenericName => "Generic name",
+            AppField::Keywords => "Keywords",
+            AppField::Categories => "Categories",
+            AppField::Comment => "Comment",
+        }
+    }
+}
+
+#[derive(Clone)]
+struct AppSearchField {
+    field: AppField,
+    // what gets shown in the subtitle when this field matches
+    display: String,
+    // lowercase terms the query gets compared against, e.g. each keyword on its own
+    terms: Vec<String>,
+}
+
+#[derive(Clone)]
+struct AppEntry {
+    result: SearchResult,
+    fields: Vec<AppSearchField>,
+}
+
+impl AppEntry {
+    // The best weighted score across all fields, and the field it came from
+    fn best_match(&self, lower_query: &str) -> (f32, &AppSearchField) {
+        let mut best = (0.0, &self.fields[0]);
+        for field in &self.fields {
+            for term in &field.terms {
+                let score = fuzzy_compare(lower_query, term) * field.field.weight();
+                if score > best.0 {
+                    best = (score, field);
+                }
+            }
+        }
+        best
+    }
+}
+
+// Installed apps and their desktop actions, from the desktop entries in the XDG data dirs
+pub struct AppsProvider {
+    // Hash map of lowercase app names to the app's search result and searchable fields
+    apps: HashMap<String, AppEntry>,
+    max_results: usize,
+    launch_history: Arc<Mutex<LaunchHistory>>,
+    frecency_weight: f32,
+}
+
+impl AppsProvider {
+    pub fn new(settings: &Settings, launch_history: Arc<Mutex<LaunchHistory>>) -> Self {
+        let locales = get_languages_from_env();
+        let entries = desktop_entries(&locales);
+        let desktops = current_desktop().unwrap_or_default();
+        // entries come in order of precedence, so the first one with an id overrides the rest,
+        // e.g. a Hidden=true copy in ~/.local/share/applications hides the system one
+        let mut seen_ids = HashSet::new();
+        let apps_list = entries.iter().flat_map(|e| {
+            let mut indexed = Vec::new();
+            if !seen_ids.insert(e.appid.clone()) {
+                return indexed;
+            }
+            let shown = if settings.app_exclude.contains(&e.appid) {
+                false
+            } else {
+                settings.app_include.contains(&e.appid) || should_show_entry(e, &desktops)
+            };
+            if !shown {
+                return indexed;
+            }
+            // the same command the launcher would run without any files, launch history goes by it
+            if let Some(exec) = e.exec().and_then(|exec| expand_exec(exec, e, &[]).ok()) {
+                let name = e.name(&locales).map(|c| c.to_string()).unwrap_or(
+                    e.generic_name(&locales)
+                        .map(|c| c.to_string())
+                        .unwrap_or(e.appid.to_string()),
+                );
+
+                // name always comes first, it's the fallback when nothing matches
+                let mut fields = vec![AppSearchField {
+                    field: AppField::Name,
+                    display: name.clone(),
+                    terms: vec![name.to_lowercase()],
+                }];
+                if let Some(generic_name) = e.generic_name(&locales) {
+                    fields.push(AppSearchField {
+                        field: AppField::GenericName,
+                        display: generic_name.to_string(),
+                        terms: vec![generic_name.to_lowercase()],
+                    });
+                }
+                // lists end with a ; so there's usually an empty one on the end
+                if let Some(keywords) = e.keywords(&locales).map(|keywords| {
+                    keywords
+                        .into_iter()
+                        .filter(|k| !k.trim().is_empty())
+                        .collect::<Vec<_>>()
+                }) && !keywords.is_empty()
+                {
+                    fields.push(AppSearchField {
+                        field: AppField::Keywords,
+                        display: keywords.join(", "),
+                        terms: keywords.iter().map(|k| k.to_lowercase()).collect(),
+                    });
+                }
+                if let Some(categories) = e.categories().map(|categories| {
+                    categories
+                        .into_iter()
+                        .filter(|c| !c.trim().is_empty())
+                        .collect::<Vec<&str>>()
+                }) && !categories.is_empty()
+                {
+                    fields.push(AppSearchField {
+                        field: AppField::Categories,
+                        display: categories.join(", "),
+                        terms: categories.iter().map(|c| c.to_lowercase()).collect(),
+                    });
+                }
+                if let Some(comment) = e.comment(&locales) {
+                    // a whole sentence never scores well against a short query, so go by word
+                    fields.push(AppSearchField {
+                        field: AppField::Comment,
+                        display: comment.to_string(),
+                        terms: comment
+                            .split_whitespace()
+                            .map(|w| w.to_lowercase())
+                            .collect(),
+                    });
+                }
+
+                // each desktop action (new window, compose message...) is a result of its own too
+                let mut actions = Vec::new();
+                for action in e.actions().unwrap_or_default() {
+                    if action.is_empty() {
+                        continue;
+                    }
+                    let (Some(action_name), Some(action_exec)) = (
+                        e.action_name(action, &locales),
+                        e.action_exec(action)
+                            .and_then(|exec| expand_exec(exec, e, &[]).ok()),
+                    ) else {
+                        continue;
+                    };
+                    let full_name = format!("{} — {}", name, action_name);
+                    let result = SearchResult {
+                        kind: ResultKind::App,
+                        name: full_name.clone(),
+                        subtitle: None,
+                        icon_path: e.icon().map(|s| s.to_string()),
+                        location: e.path.clone(),
+                        execute_command: action_exec,
+                        desktop_action: Some(action.to_owned()),
+                        actions: Vec::new(),
+                        run_command: None,
+                        match_positions: Vec::new(),
+                        activation: None,
+                    };
+                    indexed.push((
+                        full_name.to_lowercase(),
+                        AppEntry {
+                            result: result.clone(),
+                            fields: vec![AppSearchField {
+                                field: AppField::Name,
+                                display: full_name.clone(),
+                                terms: vec![full_name.to_lowercase(), action_name.to_lowercase()],
+                            }],
+                        },
+                    ));
+                    actions.push(result);
+                }
+
+                indexed.push((
+                    name.to_lowercase().clone(),
+                    AppEntry {
+                        result: SearchResult {
+                            kind: ResultKind::App,
+                            name,
+                            subtitle: None,
+                            icon_path: e.icon().map(|s| s.to_string()),
+                            location: e.path.clone(),
+                            execute_command: exec,
+                            desktop_action: None,
+                            actions,
+                            run_command: None,
+                            match_positions: Vec::new(),
+                            activation: None,
+                        },
+                        fields,
+                    },
+                ));
+            }
+            indexed
+        });
+        let apps = HashMap::from_iter(apps_list);
+        AppsProvider {
+            apps,
+            max_results: settings.max_search_results,
+            launch_history,
+            frecency_weight: settings.frecency_weight,
+        }
+    }
+}
+
+impl SearchProvider for AppsProvider {
+    fn name(&self) -> &str {
+        "apps"
+    }
+
+    fn default_prefixes(&self) -> Vec<String> {
+        vec![APPS_PREFIX.to_owned()]
+    }
+
+    fn search(&self, query: &str, handle: &SearchHandle) {
+        let mut search_results = SearchResults::new();
+        let lower_query = query.to_lowercase();
+        let history = self.launch_history.lock().unwrap();
+        let frecency_of = history.frecency_scorer();
+        let mut scored = self
+            .apps
+            .values()
+            .map(|app| {
+                let (score, field) = app.best_match(&lower_query);
+                (score, app, field)
+            })
+            // launching something a lot shouldn't make it show up for queries it doesn't match
+            .filter(|(score, _, _)| *score > 0.0)
+            .map(|(score, app, field)| {
+                // apps that get launched a lot rank higher than ones that match just as well
+                let frecency = frecency_of(&app.result.execute_command);
+                (score * (1.0 + self.frecency_weight * frecency), app, field)
+            })
+            .collect::<Vec<(f32, &AppEntry, &AppSearchField)>>();
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        for (_, app, matched) in scored.into_iter().take(self.max_results) {
+            let mut result = app.result.clone();
+            // say why it matched if it wasn't the name
+            if matched.field == AppField::Name {
+                result.match_positions = match_positions(&lower_query, &result.name);
+            } else {
+                result.subtitle = Some(format!("{}: {}", matched.field.label(), matched.display));
+            }
+            search_results.push(result);
+        }
+        handle.send(search_results);
+    }
+
+    // apps go through the launcher for Terminal=, Path= and field codes
+    fn activate(&self, result: &SearchResult) -> Option<(String, Option<Variant>)> {
+        let target = (
+            result.location.to_string_lossy().to_string(),
+            result.desktop_action.clone().unwrap_or_default(),
+            Vec::<String>::new(),
+        );
+        Some(("wlshud.launch".to_owned(), Some(target.to_variant())))
+    }
+}
+
+// Browsing the file system, starting from / or ~
+pub struct FilesProvider;
+
+impl SearchProvider for FilesProvider {
+    fn name(&self) -> &str {
+        "files"
+    }
+
+    fn default_prefixes(&self) -> Vec<String> {
+        FILES_PREFIXES.iter().map(|p| p.to_string()).collect()
+    }
+
+    fn search(&self, query: &str, handle: &SearchHandle) {
+        let path = files_query_path(&handle.prefix(), query);
+        handle.send(file_search_entries(&path, &|| handle.is_cancelled()));
+    }
+}
+
+// Whether a files prefix is the start of the path itself, like the default / and ~
+pub fn is_path_prefix(prefix: &str) -> bool {
+    prefix.starts_with(['/', '~'])
+}
+
+// What --mode files opens with, the home directory if one of the prefixes can get there
+pub fn files_mode_query(prefixes: &[String]) -> Option<String> {
+    if prefixes
+        .iter()
+        .any(|p| !p.is_empty() && "~/".starts_with(p.as_str()))
+    {
+        return Some("~/".to_owned());
+    }
+    prefixes.first().cloned()
+}
+
+// The path a files query is for, given the prefix that picked it and the rest of the query.
+// Prefixes like / and ~ are part of the path, others like f: aren't, and a path after those
+// that isn't absolute starts from the home directory.
+pub fn files_query_path(prefix: &str, rest: &str) -> String {
+    if is_path_prefix(prefix) {
+        format!("{}{}", prefix, rest)
+    } else if is_path_prefix(rest) {
+        rest.to_owned()
+    } else {
+        format!("~/{}", rest)
+    }
+}
+
+// The run prompt: the typed command in each way it can be run, then matches from the run history
+pub struct RunProvider {
+    max_results: usize,
+    run_history: Arc<Mutex<RunHistory>>,
+}
+
+impl RunProvider {
+    pub fn new(settings: &Settings, run_history: Arc<Mutex<RunHistory>>) -> Self {
+        Self {
+            max_results: settings.max_search_results,
+            run_history,
+        }
+    }
+
+    // Earlier run prompt commands matching what's typed so far, or the most recent ones when
+    // nothing is typed yet
+    fn run_history_results(&self, command_line: &str, working_dir: &Path) -> SearchResults {
+        let history = self.run_history.lock().unwrap();
+        let lower_query = command_line.to_lowercase();
+        let mut scored = history
+            .entries()
+            .iter()
+            // already covered by the rows above
+            .filter(|e| e.command_line != command_line)
+            .filter_map(|e| {
+                if lower_query.is_empty() {
+                    return Some((0.0, e));
+                }
+                let lower_command = e.command_line.to_lowercase();
+                let mut score = fuzzy_compare(&lower_query, &lower_command);
+                // typing part of an old command should always find it
+                if lower_command.contains(&lower_query) {
+                    score += 1.0;
+                }
+                (score >= MIN_RUN_HISTORY_SCORE).then_some((score, e))
+            })
+            .collect::<Vec<(f32, &RunHistoryEntry)>>();
+        // the sort is stable, so equally good matches stay most recent first
+        scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+        scored
+            .into_iter()
+            .take(self.max_results)
+            .map(|(_, e)| SearchResult {
+                kind: ResultKind::Command,
+                icon_path: Some("document-open-recent-symbolic".to_owned()),
+                name: e.command_line.clone(),
+                subtitle: Some(
+                    match e.mode {
+                        RunMode::Direct => "From run history",
+                        RunMode::Shell => "From run history, with sh -c",
+                        RunMode::Terminal => "From run history, in a terminal",
+                        RunMode::Output => "From run history, showing the output",
+                    }
+                    .to_owned(),
+                ),
+                location: working_dir.to_owned(),
+                execute_command: Vec::new(),
+                desktop_action: None,
+                actions: Vec::new(),
+                run_command: Some((e.mode, e.command_line.clone())),
+                match_positions: match_positions(&lower_query, &e.command_line),
+                activation: None,
+            })
+            .collect()
+    }
+}
+
+impl SearchProvider for RunProvider {
+    fn name(&self) -> &str {
+        "run"
+    }
+
+    fn default_prefixes(&self) -> Vec<String> {
+        vec![RUN_PREFIX.to_owned()]
+    }
+
+    fn search(&self, query: &str, handle: &SearchHandle) {
+        let mut search_results = SearchResults::new();
+        let working_dir = std::env::current_dir().expect("cannot get working directory");
+        // the prefix twice (>> by default) runs it without closing and shows what it prints
+        let prefix = handle.prefix();
+        let output_query = query
+            .strip_prefix(prefix.as_str())
+            .filter(|_| !prefix.is_empty());
+        let (command_line, modes) = match output_query {
+            Some(q) => (
+                q.trim(),
+                &[(RunMode::Output, "Run this command and show its output")][..],
+            ),
+            None => (
+                query.trim(),
+                &[
+                    (
+                        RunMode::Direct,
+                        "Run this command from the current working directory",
+                    ),
+                    (RunMode::Shell, "Run this command with sh -c"),
+                    (RunMode::Terminal, "Run this command in a terminal"),
+                ][..],
+            ),
+        };
+        if !command_line.is_empty() {
+            // quotes and escapes work like in a shell, anything fancier needs the sh -c result
+            let parse_error = parse_command_line(command_line)
+                .err()
+                .map(|e| e.to_string());
+            for &(mode, name) in modes {
+                search_results.push(SearchResult {
+                    kind: ResultKind::Command,
+                    icon_path: Some("terminal-symbolic".to_owned()),
+                    name: name.to_owned(),
+                    subtitle: parse_error.clone().filter(|_| !mode.uses_shell()),
+                    location: working_dir.clone(),
+                    execute_command: Vec::new(),
+                    desktop_action: None,
+                    actions: Vec::new(),
+                    run_command: Some((mode, command_line.to_owned())),
+                    match_positions: Vec::new(),
+                    activation: None,
+                });
+            }
+        }
+        // the typed command can show up before the history has been searched
+        handle.send(search_results);
+        handle.send(self.run_history_results(command_line, &working_dir));
+    }
+
+    fn activate(&self, result: &SearchResult) -> Option<(String, Option<Variant>)> {
+        let (mode, command_line) = result.run_command.as_ref()?;
+        Some((
+            "wlshud.run".to_owned(),
+            Some((mode.as_str(), command_line.as_str()).to_variant()),
+        ))
+    }
+}
+
+// Works out arithmetic, picking the answer copies it
+pub struct CalculatorProvider;
+
+impl SearchProvider for CalculatorProvider {
+    fn name(&self) -> &str {
+        "calculator"
+    }
+
+    fn default_prefixes(&self) -> Vec<String> {
+        CALCULATION_PREFIXES.iter().map(|p| p.to_string()).collect()
+    }
+
+    fn search(&self, query: &str, handle: &SearchHandle) {
+        let Some(calculation) = evaluate(query) else {
+            return;
+        };
+        // without a prefix every query comes through here, and just a number isn't a sum
+        if !calculation.computed && handle.prefix().is_empty() {
+            return;
+        }
+        let value = calculation.value;
+        // whole numbers are shown in the other bases too
+        let subtitle = (value.fract() == 0.0 && value >= 0.0 && value < u64::MAX as f64)
+            .then(|| format!("0x{:x} · 0b{:b}", value as u64, value as u64));
+        handle.send(vec![SearchResult {
+            kind: ResultKind::Text,
+            icon_path: Some("accessories-calculator-symbolic".to_owned()),
+            name: format_number(value),
+            subtitle,
+            location: Default::default(),
+            execute_command: Vec::new(),
+            desktop_action: None,
+            actions: Vec::new(),
+            run_command: None,
+            match_positions: Vec::new(),
+            activation: None,
+        }]);
+    }
+
+    fn activate(&self, result: &SearchResult) -> Option<(String, Option<Variant>)> {
+        Some(("wlshud.copy".to_owned(), Some(result.name.to_variant())))
+    }
+}
+
+// Unit conversions like 12 in to cm, picking one copies the converted amount
+pub struct UnitsProvider;
+
+impl SearchProvider for UnitsProvider {
+    fn name(&self) -> &str {
+        "units"
+    }
+
+    fn default_prefixes(&self) -> Vec<String> {
+        CALCULATION_PREFIXES.iter().map(|p| p.to_string()).collect()
+    }
+
+    fn search(&self, query: &str, handle: &SearchHandle) {
+        let Some(conversion) = convert(query) else {
+            return;
+        };
+        let value = format_number(conversion.value);
+        handle.send(vec![SearchResult {
+            kind: ResultKind::Text,
+            icon_path: Some("accessories-calculator-symbolic".to_owned()),
+            name: format!("{} {}", value, conversion.to),
+            subtitle: Some(format!(
+                "{} {} = {} {}",
+                format_number(conversion.amount),
+                conversion.from,
+                value,
+                conversion.to
+            )),
+            location: Default::default(),
+            execute_command: Vec::new(),
+            desktop_action: None,
+            actions: Vec::new(),
+            run_command: None,
+            match_positions: Vec::new(),
+            activation: None,
+        }]);
+    }
+
+    // just the number, the unit is easy enough to type
+    fn activate(&self, result: &SearchResult) -> Option<(String, Option<Variant>)> {
+        let (value, _unit) = result.name.split_once(' ')?;
+        Some(("wlshud.copy".to_owned(), Some(value.to_variant())))
+    }
+}
+
+// Results from a program set up under script_providers in settings.json. It's started for every
+// query with {"query": "..."} written to its stdin and prints a JSON object per line for each
+// result, which shows up as soon as it's printed. It gets killed when a newer query comes in or
+// when it runs past its timeout.
+pub struct ScriptProvider {
+    config: ScriptProviderConfig,
+    max_results: usize,
+}
+
+impl ScriptProvider {
+    pub fn new(config: ScriptProviderConfig, settings: &Settings) -> Self {
+        ScriptProvider {
+            config,
+            max_results: settings.max_search_results,
+        }
+    }
+
+    fn run(&self, query: &str, handle: &SearchHandle) -> io::Result<()> {
+        let (program, args) = self
+            .config
+            .exec
+            .split_first()
+            .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no command to run"))?;
+        let mut child = Command::new(program)
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .process_group(0)
+            .spawn()?;
+        let pid = child.id() as i32;
+        // closing stdin straight after tells it there's nothing else coming
+        if let Some(mut stdin) = child.stdin.take() {
+            let _ = writeln!(stdin, "{}", json::object! { query: query }.dump());
+        }
+
+        // read on another thread so waiting on it can give up at the timeout
+        let (sender, receiver) = mpsc::channel();
+        if let Some(stdout) = child.stdout.take() {
+            thread::spawn(move || {
+                for line in BufReader::new(stdout).lines().map_while(Result::ok) {
+                    if sender.send(line).is_err() {
+                        break;
+                    }
+                }
+            });
+        }
+        let deadline = Instant::now() + self.config.timeout;
+        let mut sent = 0;
+        while sent < self.max_results && !handle.is_cancelled() {
+            let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+                eprintln!(
+                    "{}: stopped it at its {} ms timeout",
+                    self.config.name,
+                    self.config.timeout.as_millis()
+                );
+                break;
+            };
+            let line = match receiver.recv_timeout(remaining.min(SCRIPT_POLL_INTERVAL)) {
+                Ok(line) => line,
+                Err(RecvTimeoutError::Timeout) => continue,
+                // it closed stdout, so that's everything
+                Err(RecvTimeoutError::Disconnected) => break,
+            };
+            if line.trim().is_empty() {
+                continue;
+            }
+            match parse_script_result(&line) {
+                Ok(result) => {
+                    handle.send(vec![result]);
+                    sent += 1;
+                }
+                Err(error) => eprintln!("{}: skipping result: {}", self.config.name, error),
+            }
+        }
+
+        // anything it started in the meantime goes too
+        // SAFETY: the pid is still ours until wait() below reaps it
+        unsafe {
+            libc::kill(-pid, libc::SIGKILL);
+        }
+        child.wait()?;
+        Ok(())
+    }
+}
+
+impl SearchProvider for ScriptProvider {
+    fn name(&self) -> &str {
+        &self.config.name
+    }
+
+    fn default_prefixes(&self) -> Vec<String> {
+        self.config.prefixes.clone()
+    }
+
+    fn search(&self, query: &str, handle: &SearchHandle) {
+        if let Err(error) = self.run(query, handle) {
+            handle.send(vec![SearchResult {
+                kind: ResultKind::Text,
+                icon_path: Some("dialog-error-symbolic".to_owned()),
+                name: format!("{} couldn't search", self.config.name),
+                subtitle: Some(error.to_string()),
+                location: Default::default(),
+                execute_command: Vec::new(),
+                desktop_action: None,
+                actions: Vec::new(),
+                run_command: None,
+                match_positions: Vec::new(),
+                activation: None,
+            }]);
+        }
+    }
+}
+
+// One line of a script provider's output. Results need a name and either an "exec" command (an
+// argument list or a command line) or an "action" of the window to run, with an optional string
+// "target".
+fn parse_script_result(line: &str) -> Result<SearchResult, String> {
+    let data = json::parse(line).map_err(|error| error.to_string())?;
+    let name = data["name"].as_str().ok_or("it has no \"name\"")?;
+    let execute_command = if let Some(command_line) = data["exec"].as_str() {
+        parse_command_line(command_line).map_err(|error| error.to_string())?
+    } else {
+        data["exec"]
+            .members()
+            .filter_map(|m| m.as_str())
+            .map(|arg| arg.to_owned())
+            .collect()
+    };
+    let activation = data["action"].as_str().map(|action| {
+        (
+            format!("wlshud.{}", action),
+            data["target"].as_str().map(|target| target.to_variant()),
+        )
+    });
+    if execute_command.is_empty() && activation.is_none() {
+        return Err(format!("{} has no \"exec\" or \"action\"", name));
+    }
+    Ok(SearchResult {
+        kind: ResultKind::Text,
+        icon_path: data["icon"].as_str().map(|icon| icon.to_owned()),
+        name: name.to_owned(),
+        subtitle: data["subtitle"]
+            .as_str()
+            .map(|subtitle| subtitle.to_owned()),
+        location: Default::default(),
+        execute_command,
+        desktop_action: None,
+        actions: Vec::new(),
+        run_command: None,
+        match_positions: Vec::new(),
+        activation,
+    })
+}
@@ -1,140 +1,654 @@
-use std::{collections::HashMap, fs, path::PathBuf};
+use std::{
+    cell::{Cell, RefCell},
+    fs,
+    path::PathBuf,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicU64, Ordering},
+    },
+    thread,
+};
 
-use freedesktop_desktop_entry::{desktop_entries, get_languages_from_env};
+use freedesktop_desktop_entry::DesktopEntry;
 use gtk4::{
-    Box, Button, Image, Label, ListBox, ListBoxRow, Widget,
-    glib::{object::IsA, variant::ToVariant},
-    prelude::{BoxExt, ListBoxRowExt, WidgetExt},
+    Box, Button, EventControllerKey, Image, Label, ListBox, ListBoxRow, ScrolledWindow,
+    SearchEntry, ToggleButton,
+    gdk::{Key, ModifierType},
+    glib::{
+        self, Variant, clone,
+        object::{Cast, CastNone},
+        variant::ToVariant,
+    },
+    prelude::{
+        ActionableExt, AdjustmentExt, BoxExt, ButtonExt, ListBoxRowExt, ToggleButtonExt, WidgetExt,
+    },
 };
-use rust_fuzzy_search::fuzzy_search_best_n;
-
-use crate::icon_from_name;
+use rust_fuzzy_search::fuzzy_compare;
 
-// TODO: User-customizable?
-const MAX_SEARCH_RESULTS: usize = 20;
+use crate::{
+    config::Settings,
+    history::{LaunchHistory, RunHistory},
+    icon_from_name,
+    launcher::{RunMode, is_executable_in_path},
+    providers::{
+        AppsProvider, CalculatorProvider, FilesProvider, RunProvider, ScriptProvider,
+        SearchProvider, UnitsProvider,
+    },
+};
 
 pub type SearchResults = Vec<SearchResult>;
 
+#[derive(Clone, Copy, PartialEq)]
+pub enum ResultKind {
+    // launched through its desktop entry at `location`
+    App,
+    // can also be opened with a chosen app
+    File,
+    Command,
+    // just a line of text, e.g. in dmenu mode
+    Text,
+}
+
 #[derive(Clone)]
 pub struct SearchResult {
+    pub kind: ResultKind,
     pub icon_path: Option<String>,
     pub name: String,
+    // shown under the name instead of the location when set
+    pub subtitle: Option<String>,
     pub location: PathBuf,
     pub execute_command: Vec<String>,
+    // id of the [Desktop Action ...] group this runs, if it isn't the main entry
+    pub desktop_action: Option<String>,
+    // desktop actions of an app, shown under its row when expanded
+    pub actions: Vec<SearchResult>,
+    // for the run prompt, how to run it and the command as typed, remembered in the run history
+    pub run_command: Option<(RunMode, String)>,
+    // characters of the name that matched the query, as char indices, shown highlighted
+    pub match_positions: Vec<usize>,
+    // the window action picking it runs and its target if it takes one, filled in by its
+    // provider when unset
+    pub activation: Option<(String, Option<Variant>)>,
 }
 
-#[derive(Clone)]
+// Every search provider, with the prefixes that pick it
 pub struct SearchDatabase {
-    // Hash map of app names to the full search result w/info
-    apps: HashMap<String, SearchResult>,
+    providers: Vec<(Arc<dyn SearchProvider>, Vec<String>)>,
 }
 
 impl SearchDatabase {
-    pub fn new() -> Self {
-        let locales = get_languages_from_env();
-        let entries = desktop_entries(&locales);
-        let apps_list = entries.iter().filter_map(|e| {
-            if let Ok(exec) = e.parse_exec() {
-                let name = e.name(&locales).map(|c| c.to_string()).unwrap_or(
-                    e.generic_name(&locales)
-                        .map(|c| c.to_string())
-                        .unwrap_or(e.appid.to_string()),
-                );
-                Some((
-                    name.to_lowercase().clone(),
-                    SearchResult {
-                        name,
-                        icon_path: e.icon().map(|s| s.to_string()),
-                        location: e.path.clone(),
-                        execute_command: exec,
-                    },
-                ))
-            } else {
-                None
+    pub fn new(
+        settings: &Settings,
+        launch_history: Arc<Mutex<LaunchHistory>>,
+        run_history: Arc<Mutex<RunHistory>>,
+    ) -> Self {
+        // earlier ones come first when several share a prefix
+        let mut providers: Vec<Arc<dyn SearchProvider>> = vec![
+            Arc::new(FilesProvider),
+            Arc::new(RunProvider::new(settings, run_history)),
+            // before apps so an answer ends up on top
+            Arc::new(CalculatorProvider),
+            Arc::new(UnitsProvider),
+            Arc::new(AppsProvider::new(settings, launch_history)),
+        ];
+        // scripts go last so a slow one never holds up the built in results
+        for config in &settings.script_providers {
+            providers.push(Arc::new(ScriptProvider::new(config.clone(), settings)));
+        }
+        let providers = providers
+            .into_iter()
+            .filter_map(|provider| {
+                // a provider set to false is turned off
+                let prefixes = match settings.search_prefixes.get(provider.name()) {
+                    Some(prefixes) => prefixes.clone()?,
+                    None => provider.default_prefixes(),
+                };
+                Some((provider, prefixes))
+            })
+            .collect();
+        SearchDatabase { providers }
+    }
+
+    // Hands the query to the providers with the longest prefix it starts with, so e.g. a
+    // provider on >> would take those queries from the one on >
+    pub fn search(&self, query: &str, handle: &SearchHandle) {
+        let longest = self
+            .providers
+            .iter()
+            .flat_map(|(_, prefixes)| prefixes)
+            .filter(|prefix| query.starts_with(prefix.as_str()))
+            .map(|prefix| prefix.len())
+            .max();
+        if let Some(longest) = longest {
+            for (provider, prefixes) in &self.providers {
+                if handle.is_cancelled() {
+                    return;
+                }
+                if !prefixes
+                    .iter()
+                    .any(|p| p.len() == longest && query.starts_with(p.as_str()))
+                {
+                    continue;
+                }
+                let provider_query = if provider.strips_prefix() {
+                    &query[longest..]
+                } else {
+                    query
+                };
+                handle.set_provider(provider, &query[..longest]);
+                provider.search(provider_query, handle);
             }
+        }
+        handle.finish();
+    }
+}
+
+// The desktop entry spec's visibility rules, `desktops` being XDG_CURRENT_DESKTOP in lowercase
+pub fn should_show_entry(entry: &DesktopEntry, desktops: &[String]) -> bool {
+    if entry.no_display() || entry.hidden() {
+        return false;
+    }
+    let in_current_desktop = |list: Vec<&str>| {
+        list.iter().any(|d| {
+            desktops
+                .iter()
+                .any(|current| d.eq_ignore_ascii_case(current))
+        })
+    };
+    if let Some(only_show_in) = entry.only_show_in()
+        && !in_current_desktop(only_show_in)
+    {
+        return false;
+    }
+    if let Some(not_show_in) = entry.not_show_in()
+        && in_current_desktop(not_show_in)
+    {
+        return false;
+    }
+    // TryExec names a binary that has to be installed for the entry to be usable
+    match entry.try_exec() {
+        Some(program) if !program.is_empty() => is_executable_in_path(program),
+        _ => true,
+    }
+}
+
+// Scores every name against the query (case insensitive) and returns the indices of the
+// best matches, best first. Names that don't match at all are left out.
+pub fn fuzzy_match_indices(query: &str, names: &[&str], max_results: usize) -> Vec<usize> {
+    let lower_query = query.to_lowercase();
+    let mut scored = names
+        .iter()
+        .enumerate()
+        .map(|(idx, name)| (idx, fuzzy_compare(&lower_query, &name.to_lowercase())))
+        .filter(|(_, score)| *score > 0.0)
+        .collect::<Vec<(usize, f32)>>();
+    scored.sort_by(|a, b| b.1.total_cmp(&a.1));
+    scored
+        .into_iter()
+        .take(max_results)
+        .map(|(idx, _)| idx)
+        .collect()
+}
+
+// Which characters of `name` the query matched (case insensitive), as char indices. The query as
+// one piece if it's in there, otherwise its characters in order. Nothing if neither works, since
+// the fuzzy score can come from bits of the query that match out of order.
+pub fn match_positions(query: &str, name: &str) -> Vec<usize> {
+    // lowercase char by char so the indices keep lining up with the name
+    let lower = |s: &str| {
+        s.chars()
+            .map(|c| c.to_lowercase().next().unwrap_or(c))
+            .collect::<Vec<char>>()
+    };
+    let query = lower(query);
+    let name = lower(name);
+    if query.is_empty() || query.len() > name.len() {
+        return Vec::new();
+    }
+    if let Some(start) = name
+        .windows(query.len())
+        .position(|w| w == query.as_slice())
+    {
+        return (start..start + query.len()).collect();
+    }
+    let mut positions = Vec::new();
+    let mut query_chars = query.iter().peekable();
+    for (idx, c) in name.iter().enumerate() {
+        if query_chars.next_if_eq(&c).is_some() {
+            positions.push(idx);
+        }
+    }
+    if query_chars.peek().is_some() {
+        return Vec::new();
+    }
+    positions
+}
+
+// Pango markup for `name` with the characters at `positions` in bold
+fn highlighted_markup(name: &str, positions: &[usize]) -> String {
+    let mut markup = String::new();
+    let mut highlighted = false;
+    for (idx, c) in name.chars().enumerate() {
+        let matched = positions.contains(&idx);
+        if matched != highlighted {
+            markup.push_str(if matched { "<b>" } else { "</b>" });
+            highlighted = matched;
+        }
+        markup.push_str(&glib::markup_escape_text(c.encode_utf8(&mut [0; 4])));
+    }
+    if highlighted {
+        markup.push_str("</b>");
+    }
+    markup
+}
+
+// A search that's been sent to the SearchWorker, for checking whether it's still wanted and
+// sending back what it finds
+pub struct SearchHandle {
+    generation: u64,
+    latest: Arc<AtomicU64>,
+    updates: async_channel::Sender<SearchUpdate>,
+    // the provider whose results are being sent, for filling in how to activate them
+    provider: RefCell<Option<Arc<dyn SearchProvider>>>,
+    // the prefix that picked it, empty for queries no other prefix claims
+    prefix: RefCell<String>,
+    sent_any: Cell<bool>,
+}
+
+impl SearchHandle {
+    // true once a newer query has come in, searches should stop early when they see this
+    pub fn is_cancelled(&self) -> bool {
+        self.latest.load(Ordering::SeqCst) != self.generation
+    }
+
+    pub fn send(&self, mut results: SearchResults) {
+        if results.is_empty() || self.is_cancelled() {
+            return;
+        }
+        if let Some(provider) = self.provider.borrow().as_ref() {
+            for result in results.iter_mut() {
+                fill_activation(result, provider.as_ref());
+            }
+        }
+        self.sent_any.set(true);
+        let _ = self.updates.send_blocking(SearchUpdate {
+            generation: self.generation,
+            results,
         });
-        let apps = HashMap::from_iter(apps_list);
-        SearchDatabase { apps }
-    }
-
-    pub fn search(&self, query: &str) -> SearchResults {
-        let mut search_results = SearchResults::new();
-        if query.starts_with('/') || query.starts_with('~') {
-            let mut maybe_entries = get_file_search_entries(query);
-            search_results.append(&mut maybe_entries);
-        } else if let Some(q) = query.strip_prefix('>') {
-            search_results.push(SearchResult {
-                icon_path: Some("terminal-symbolic".to_owned()),
-                name: "Run this command from the current working directory".to_owned(),
-                location: std::env::current_dir().expect("cannot get working directory"),
-                execute_command: q.split(' ').map(|s| s.to_owned()).collect(),
+    }
+
+    pub fn prefix(&self) -> String {
+        self.prefix.borrow().clone()
+    }
+
+    fn set_provider(&self, provider: &Arc<dyn SearchProvider>, prefix: &str) {
+        self.provider.replace(Some(provider.clone()));
+        self.prefix.replace(prefix.to_owned());
+    }
+
+    // The first batch of a search replaces the results of the last one, so if nothing was found
+    // an empty one still has to go out to clear them
+    fn finish(&self) {
+        if !self.sent_any.get() && !self.is_cancelled() {
+            let _ = self.updates.send_blocking(SearchUpdate {
+                generation: self.generation,
+                results: Vec::new(),
             });
-        } else {
-            let app_names = self.apps.keys().map(|s| s.as_str()).collect::<Vec<&str>>();
-            let lower_search = query.to_ascii_lowercase();
-            let results =
-                fuzzy_search_best_n(&lower_search, app_names.as_slice(), MAX_SEARCH_RESULTS);
-            for result in results {
-                // should be a guaranteed success
-                if let Some(app) = self.apps.get(result.0) {
-                    search_results.push(app.clone());
+        }
+    }
+}
+
+fn fill_activation(result: &mut SearchResult, provider: &dyn SearchProvider) {
+    if result.activation.is_none() {
+        result.activation = provider.activate(result);
+    }
+    for action in result.actions.iter_mut() {
+        fill_activation(action, provider);
+    }
+}
+
+pub struct SearchUpdate {
+    // which query these are for, queries are numbered in the order they're typed
+    pub generation: u64,
+    pub results: SearchResults,
+}
+
+// Runs searches on a thread of their own, so a slow one (like listing a directory on a network
+// mount) never blocks typing. A search that's been overtaken by a newer query stops early and
+// anything it already sent is dropped.
+pub struct SearchWorker {
+    queries: async_channel::Sender<(u64, String)>,
+    latest: Arc<AtomicU64>,
+}
+
+// The other end of a SearchWorker, giving back results as they're found
+pub struct SearchUpdates {
+    updates: async_channel::Receiver<SearchUpdate>,
+    latest: Arc<AtomicU64>,
+}
+
+impl SearchWorker {
+    pub fn new(database: SearchDatabase) -> (Self, SearchUpdates) {
+        let (query_sender, query_receiver) = async_channel::unbounded::<(u64, String)>();
+        let (update_sender, update_receiver) = async_channel::unbounded();
+        let latest = Arc::new(AtomicU64::new(0));
+        let worker_latest = latest.clone();
+        // stops once the SearchWorker is dropped and there are no more queries coming
+        thread::spawn(move || {
+            while let Ok((generation, query)) = query_receiver.recv_blocking() {
+                let handle = SearchHandle {
+                    generation,
+                    latest: worker_latest.clone(),
+                    updates: update_sender.clone(),
+                    provider: RefCell::new(None),
+                    prefix: RefCell::new(String::new()),
+                    sent_any: Cell::new(false),
+                };
+                // queries that piled up while the last search ran are already out of date
+                if !handle.is_cancelled() {
+                    database.search(&query, &handle);
                 }
             }
-        }
+        });
+        (
+            Self {
+                queries: query_sender,
+                latest: latest.clone(),
+            },
+            SearchUpdates {
+                updates: update_receiver,
+                latest,
+            },
+        )
+    }
 
-        search_results
+    // Starts searching for `query`, cancelling whatever search is still running
+    pub fn search(&self, query: &str) {
+        let generation = self.latest.fetch_add(1, Ordering::SeqCst) + 1;
+        let _ = self.queries.send_blocking((generation, query.to_owned()));
+    }
+
+    // Stops the running search without starting another, e.g. once the entry gets cleared
+    pub fn cancel(&self) {
+        self.latest.fetch_add(1, Ordering::SeqCst);
+    }
+}
+
+impl SearchUpdates {
+    // The next batch of results for the newest query, None once the worker is gone
+    pub async fn next(&self) -> Option<SearchUpdate> {
+        while let Ok(update) = self.updates.recv().await {
+            if self.latest.load(Ordering::SeqCst) == update.generation {
+                return Some(update);
+            }
+        }
+        None
     }
 }
 
-pub fn build_search_results(results: SearchResults) -> impl IsA<Widget> {
+pub fn build_search_results(results: SearchResults) -> ListBox {
     let list_box = ListBox::builder()
         .activate_on_single_click(true)
         .selection_mode(gtk4::SelectionMode::Single)
         .show_separators(true)
         .build();
+    update_search_results(&list_box, results, false);
 
+    list_box
+}
+
+// Shows `results` in a list from build_search_results, after the rows already there if `append`
+// is set and in place of them otherwise. The rows get reused instead of building a new list,
+// so nothing flickers or jumps back to the top while results stream in.
+pub fn update_search_results(list_box: &ListBox, results: SearchResults, append: bool) {
+    let mut idx = if append {
+        (0..).map_while(|i| list_box.row_at_index(i)).count() as i32
+    } else {
+        0
+    };
     for result in results {
+        let (_, row_contents) = set_result_row(list_box, idx, &result, false);
+        idx += 1;
+        if result.actions.is_empty() {
+            continue;
+        }
+
+        // Desktop actions go in rows of their own right under the app, hidden until expanded
+        let action_rows = result
+            .actions
+            .iter()
+            .map(|action| {
+                let (action_row, _) = set_result_row(list_box, idx, action, true);
+                idx += 1;
+                action_row
+            })
+            .collect::<Vec<ListBoxRow>>();
+        let expand_button = ToggleButton::builder()
+            .icon_name("pan-down-symbolic")
+            .tooltip_text("Show actions")
+            .valign(gtk4::Align::Center)
+            .build();
+        expand_button.connect_toggled(move |button| {
+            let expanded = button.is_active();
+            button.set_icon_name(if expanded {
+                "pan-up-symbolic"
+            } else {
+                "pan-down-symbolic"
+            });
+            for action_row in &action_rows {
+                action_row.set_visible(expanded);
+            }
+        });
+        row_contents.append(&expand_button);
+    }
+    if !append {
+        // whatever's left is from the last search
+        while let Some(row) = list_box.row_at_index(idx) {
+            list_box.remove(&row);
+        }
+    }
+    // so Enter has something to run and the arrow keys somewhere to start from
+    if !append || list_box.selected_row().is_none() {
+        list_box.select_row(list_box.row_at_index(0).as_ref());
+    }
+}
+
+// Lets the keyboard drive the results without leaving the search entry. Up/Down or Ctrl+P/Ctrl+N
+// move the selection, Alt+1..9 run one of the first nine rows and Ctrl+S makes a shortcut from
+// the selected one. Enter is left to the entry's activate, see selected_result_row.
+pub fn add_result_keybinds(entry: &SearchEntry, results_window: &ScrolledWindow) {
+    // capture, so the entry doesn't get to use the arrow keys first
+    let key_controller = EventControllerKey::builder()
+        .propagation_phase(gtk4::PropagationPhase::Capture)
+        .build();
+    key_controller.connect_key_pressed(clone!(
+        #[weak]
+        results_window,
+        #[upgrade_or]
+        glib::Propagation::Proceed,
+        move |_, key, _, modifiers| {
+            let Some(list_box) = results_list(&results_window) else {
+                return glib::Propagation::Proceed;
+            };
+            let ctrl = modifiers.contains(ModifierType::CONTROL_MASK);
+            let alt = modifiers.contains(ModifierType::ALT_MASK);
+            if key == Key::Down || (ctrl && key == Key::n) {
+                move_selection(&list_box, &results_window, 1);
+            } else if key == Key::Up || (ctrl && key == Key::p) {
+                move_selection(&list_box, &results_window, -1);
+            } else if alt
+                && let Some(n) = key.to_unicode().and_then(|c| c.to_digit(10))
+                && n > 0
+            {
+                if let Some(row) = visible_rows(&list_box).nth(n as usize - 1) {
+                    row.activate();
+                }
+            } else if ctrl
+                && key == Key::s
+                && let Some(button) = list_box
+                    .selected_row()
+                    .and_then(|row| create_shortcut_button(&row))
+            {
+                button.emit_clicked();
+            } else {
+                return glib::Propagation::Proceed;
+            }
+            glib::Propagation::Stop
+        }
+    ));
+    entry.add_controller(key_controller);
+}
+
+// The row Enter should run, the selected one or else the first
+pub fn selected_result_row(results_window: &ScrolledWindow) -> Option<ListBoxRow> {
+    let list_box = results_list(results_window)?;
+    list_box.selected_row().or_else(|| list_box.row_at_index(0))
+}
+
+// Only while the results are actually showing
+fn results_list(results_window: &ScrolledWindow) -> Option<ListBox> {
+    results_window.parent()?;
+    // there's a GtkViewport in between these for some reason
+    results_window
+        .child()
+        .and_then(|c| c.first_child())
+        .and_downcast::<ListBox>()
+}
+
+// Rows that aren't hidden, desktop actions only count while their app is expanded
+fn visible_rows(list_box: &ListBox) -> impl Iterator<Item = ListBoxRow> {
+    let list_box = list_box.clone();
+    (0..)
+        .map_while(move |i| list_box.row_at_index(i))
+        .filter(|row| row.is_visible())
+}
+
+fn move_selection(list_box: &ListBox, results_window: &ScrolledWindow, step: i32) {
+    let rows = visible_rows(list_box).collect::<Vec<ListBoxRow>>();
+    if rows.is_empty() {
+        return;
+    }
+    let next = match list_box
+        .selected_row()
+        .and_then(|selected| rows.iter().position(|r| *r == selected))
+    {
+        Some(i) => (i as i32 + step).clamp(0, rows.len() as i32 - 1) as usize,
+        None => 0,
+    };
+    let row = &rows[next];
+    list_box.select_row(Some(row));
+    // scroll it into view, grabbing focus would take it away from the entry
+    if let Some(bounds) = row.compute_bounds(list_box) {
+        results_window
+            .vadjustment()
+            .clamp_page(bounds.y() as f64, (bounds.y() + bounds.height()) as f64);
+    }
+}
+
+fn create_shortcut_button(row: &ListBoxRow) -> Option<Button> {
+    let mut child = row.child()?.first_child();
+    while let Some(c) = child {
+        if let Ok(button) = c.clone().downcast::<Button>()
+            && button.action_name().as_deref() == Some("wlshud.create-shortcut")
+        {
+            return Some(button);
+        }
+        child = c.next_sibling();
+    }
+    None
+}
+
+// Fills in the row at `idx` with `result`, adding a row to the end if there aren't that many
+fn set_result_row(
+    list_box: &ListBox,
+    idx: i32,
+    result: &SearchResult,
+    nested: bool,
+) -> (ListBoxRow, Box) {
+    let row = list_box.row_at_index(idx).unwrap_or_else(|| {
         let row = ListBoxRow::builder()
             .selectable(true)
             .activatable(true)
-            .action_name("wlshud.exec")
-            .action_target(&result.execute_command.to_variant())
-            .build();
-        const ROW_SPACING_MARGIN: i32 = 8;
-        let row_contents = Box::builder()
-            .orientation(gtk4::Orientation::Horizontal)
-            .margin_bottom(ROW_SPACING_MARGIN)
-            .margin_top(ROW_SPACING_MARGIN)
-            .margin_end(ROW_SPACING_MARGIN)
-            .margin_start(ROW_SPACING_MARGIN)
-            .vexpand(true)
-            .spacing(16)
-            .build();
-        let labels_box = Box::builder()
-            .orientation(gtk4::Orientation::Vertical)
             .build();
-        let name_label = Label::new(Some(&result.name));
-        name_label.set_halign(gtk4::Align::Start);
+        list_box.append(&row);
+        row
+    });
+    // desktop actions start out hidden, see update_search_results
+    row.set_visible(!nested);
+    if let Some((action_name, target)) = &result.activation {
+        row.set_action_name(Some(action_name));
+        row.set_action_target_value(target.as_ref());
+    } else {
+        row.set_action_name(Some("wlshud.exec"));
+        row.set_action_target_value(Some(&result.execute_command.to_variant()));
+    }
+    const ROW_SPACING_MARGIN: i32 = 8;
+    let row_contents = Box::builder()
+        .orientation(gtk4::Orientation::Horizontal)
+        .margin_bottom(ROW_SPACING_MARGIN)
+        .margin_top(ROW_SPACING_MARGIN)
+        .margin_end(ROW_SPACING_MARGIN)
+        // indent desktop actions under their app
+        .margin_start(if nested {
+            ROW_SPACING_MARGIN * 6
+        } else {
+            ROW_SPACING_MARGIN
+        })
+        .vexpand(true)
+        .spacing(16)
+        .build();
+    let labels_box = Box::builder()
+        .orientation(gtk4::Orientation::Vertical)
+        .build();
+    let name_label = Label::new(None);
+    name_label.set_markup(&highlighted_markup(&result.name, &result.match_positions));
+    name_label.add_css_class("search-result-name");
+    name_label.set_halign(gtk4::Align::Start);
+    labels_box.append(&name_label);
+
+    if result.kind == ResultKind::Text {
+        if let Some(subtitle) = &result.subtitle {
+            let subtitle_label = Label::new(Some(subtitle));
+            subtitle_label.set_css_classes(&["subtitle"]);
+            subtitle_label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
+            subtitle_label.set_halign(gtk4::Align::Start);
+            labels_box.append(&subtitle_label);
+        }
+        if let Some(path) = &result.icon_path {
+            let icon = icon_from_name(path);
+            icon.set_icon_size(gtk4::IconSize::Large);
+            row_contents.append(&icon);
+        }
+        row_contents.append(&labels_box);
+    } else {
         let file_path = result
             .location
             .to_str()
             .expect("Invalid path from searches");
-        let location_label = Label::new(Some(file_path));
+        let location_label = Label::new(Some(result.subtitle.as_deref().unwrap_or(file_path)));
         location_label.set_css_classes(&["subtitle"]);
+        location_label.set_tooltip_text(Some(file_path));
+        location_label.set_ellipsize(gtk4::pango::EllipsizeMode::End);
         location_label.set_halign(gtk4::Align::Start);
-        labels_box.append(&name_label);
         labels_box.append(&location_label);
 
+        // the target is the file and which desktop action to use, empty for the main one
         let create_shortcut_button = Button::builder()
             .icon_name("plus-symbolic")
             .action_name("wlshud.create-shortcut")
-            .action_target(&file_path.to_variant())
+            .action_target(
+                &(
+                    file_path,
+                    result.desktop_action.as_deref().unwrap_or_default(),
+                )
+                    .to_variant(),
+            )
             .halign(gtk4::Align::End)
             .hexpand(true)
             .build();
 
-        let icon = if let Some(path) = result.icon_path {
-            icon_from_name(&path)
+        let icon = if let Some(path) = &result.icon_path {
+            icon_from_name(path)
         } else {
             Image::from_icon_name("external-link-symbolic")
         };
@@ -144,20 +658,34 @@ pub fn build_search_results(results: SearchResults) -> impl IsA<Widget> {
         row_contents.append(&labels_box);
         row_contents.append(&create_shortcut_button);
 
-        row.set_child(Some(&row_contents));
-        list_box.append(&row);
+        if result.kind == ResultKind::File {
+            let open_with_button = Button::builder()
+                .label("Open with…")
+                .css_classes(["flat"])
+                .action_name("wlshud.open-with")
+                .action_target(&file_path.to_variant())
+                .build();
+            row_contents.append(&open_with_button);
+        }
     }
 
-    list_box
+    row.set_child(Some(&row_contents));
+    (row, row_contents)
 }
 
 pub fn get_file_search_entries(query: &str) -> Vec<SearchResult> {
+    file_search_entries(query, &|| false)
+}
+
+// Stops listing the directory and returns nothing once `cancelled` says to
+pub fn file_search_entries(query: &str, cancelled: &dyn Fn() -> bool) -> Vec<SearchResult> {
     let mut maybe_entries = Vec::new();
     let last_slash = query.rfind('/').unwrap_or(0);
     let (mut path_str, mut file_portion) = if last_slash > 0 {
         query.split_at(query.rfind('/').unwrap_or(0))
     } else {
-        query.split_at(1)
+        // the path is always at least its first character, / or ~
+        query.split_at(query.chars().next().map_or(0, char::len_utf8))
     };
     if file_portion.starts_with('/') {
         file_portion = &file_portion[1..];
@@ -174,7 +702,11 @@ pub fn get_file_search_entries(query: &str) -> Vec<SearchResult> {
     let files = fs::read_dir(path_str);
     if let Ok(iter) = files {
         for entry in iter.flatten() {
+            if cancelled() {
+                return Vec::new();
+            }
             maybe_entries.push(SearchResult {
+                kind: ResultKind::File,
                 icon_path: entry.file_type().ok().and_then(|t| {
                     if t.is_dir() {
                         Some("folder".to_owned())
@@ -186,17 +718,27 @@ pub fn get_file_search_entries(query: &str) -> Vec<SearchResult> {
                     .file_name()
                     .into_string()
                     .unwrap_or("Corrupt File".to_owned()),
+                subtitle: None,
                 location: entry.path(),
                 execute_command: vec![
                     "xdg-open".to_owned(),
                     format!("{}/{}", path_str, entry.file_name().display()),
                 ],
+                desktop_action: None,
+                actions: Vec::new(),
+                run_command: None,
+                match_positions: Vec::new(),
+                activation: None,
             });
         }
         maybe_entries = maybe_entries
             .iter()
             .filter(|e| e.name.starts_with(file_portion))
             .cloned()
+            .map(|mut e| {
+                e.match_positions = (0..file_portion.chars().count()).collect();
+                e
+            })
             .collect();
         maybe_entries.sort_by(|a, b| a.name.cmp(&b.name));
     }
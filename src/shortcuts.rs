@@ -11,14 +11,19 @@ use crate::{config::ShortcutNode, icon_from_name};
 
 // #[derive(Default)]
 pub struct ShortcutsDisplay {
+    root_nodes: RefCell<Vec<ShortcutNode>>,
     current_nodes: RefCell<Vec<ShortcutNode>>,
+    // characters typed to get to current_nodes
+    current_path: RefCell<String>,
     outer_box: Box,
 }
 
 impl Default for ShortcutsDisplay {
     fn default() -> Self {
         Self {
+            root_nodes: RefCell::new(Vec::new()),
             current_nodes: RefCell::new(Vec::new()),
+            current_path: RefCell::new(String::new()),
             outer_box: Box::builder()
                 .orientation(gtk4::Orientation::Vertical)
                 .vexpand(true)
@@ -32,12 +37,8 @@ impl Default for ShortcutsDisplay {
 
 impl ShortcutsDisplay {
     pub fn new(nodes_list: &[ShortcutNode]) -> Self {
-        let s = Self {
-            current_nodes: RefCell::new(nodes_list.to_owned()),
-            ..Default::default()
-        };
-        let row_1 = build_shortcuts_row(&s.current_nodes.borrow());
-        s.box_widget().append(&row_1);
+        let s = Self::default();
+        s.reload(nodes_list);
 
         s
     }
@@ -46,11 +47,13 @@ impl ShortcutsDisplay {
         let mut swap_node = None;
         for child in cur_nodes.iter() {
             if child.character == key {
-                if let Some(exec) = &child.exec {
+                if let Some(exec) = child.command() {
+                    // the path comes along so a failing command can be edited
+                    let path = format!("{}{}", self.current_path.borrow(), key);
                     let _ = <Box as WidgetExt>::activate_action(
                         &self.outer_box,
-                        "wlshud.exec",
-                        Some(&exec.to_variant()),
+                        "wlshud.exec-shortcut",
+                        Some(&(path, exec).to_variant()),
                     );
                 } else if !child.children.is_empty() {
                     // Activate children
@@ -80,6 +83,7 @@ impl ShortcutsDisplay {
                 .build();
             self.outer_box.append(&row);
             self.current_nodes.set(node.children.clone());
+            self.current_path.borrow_mut().push(key);
             fade_in.play();
             true
         } else {
@@ -87,6 +91,23 @@ impl ShortcutsDisplay {
         }
     }
 
+    // Swaps in a new set of shortcuts, used when shortcuts.json changes
+    pub fn reload(&self, nodes_list: &[ShortcutNode]) {
+        self.root_nodes.set(nodes_list.to_owned());
+        self.reset();
+    }
+
+    // Goes back to the top level row
+    pub fn reset(&self) {
+        while let Some(child) = self.outer_box.first_child() {
+            self.outer_box.remove(&child);
+        }
+        let root_nodes = self.root_nodes.borrow();
+        self.current_nodes.set(root_nodes.clone());
+        self.current_path.borrow_mut().clear();
+        self.outer_box.append(&build_shortcuts_row(&root_nodes));
+    }
+
     pub fn box_widget(&self) -> &Box {
         &self.outer_box
     }
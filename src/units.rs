@@ -0,0 +1,406 @@
+// Converts between units typed into the search bar, like 12 in to cm or 3 GiB in MB. Everything
+// is offline from the table below.
+
+use crate::calculator::evaluate;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Dimension {
+    Length,
+    Area,
+    Volume,
+    Mass,
+    Time,
+    Speed,
+    Temperature,
+    Data,
+}
+
+struct Unit {
+    // the first one is what's shown, they're all matched case insensitively
+    names: &'static [&'static str],
+    dimension: Dimension,
+    // value in the dimension's base unit (metres, kelvin, bytes...) = value * factor + offset
+    factor: f64,
+    offset: f64,
+}
+
+const fn unit(names: &'static [&'static str], dimension: Dimension, factor: f64) -> Unit {
+    Unit {
+        names,
+        dimension,
+        factor,
+        offset: 0.0,
+    }
+}
+
+const UNITS: &[Unit] = &[
+    // metres
+    unit(
+        &["nm", "nanometre", "nanometres", "nanometer", "nanometers"],
+        Dimension::Length,
+        1e-9,
+    ),
+    unit(
+        &[
+            "µm",
+            "um",
+            "micrometre",
+            "micrometres",
+            "micrometer",
+            "micrometers",
+            "micron",
+            "microns",
+        ],
+        Dimension::Length,
+        1e-6,
+    ),
+    unit(
+        &[
+            "mm",
+            "millimetre",
+            "millimetres",
+            "millimeter",
+            "millimeters",
+        ],
+        Dimension::Length,
+        1e-3,
+    ),
+    unit(
+        &[
+            "cm",
+            "centimetre",
+            "centimetres",
+            "centimeter",
+            "centimeters",
+        ],
+        Dimension::Length,
+        1e-2,
+    ),
+    unit(
+        &["m", "metre", "metres", "meter", "meters"],
+        Dimension::Length,
+        1.0,
+    ),
+    unit(
+        &["km", "kilometre", "kilometres", "kilometer", "kilometers"],
+        Dimension::Length,
+        1e3,
+    ),
+    unit(&["in", "inch", "inches", "\""], Dimension::Length, 0.0254),
+    unit(&["ft", "foot", "feet", "'"], Dimension::Length, 0.3048),
+    unit(&["yd", "yard", "yards"], Dimension::Length, 0.9144),
+    unit(&["mi", "mile", "miles"], Dimension::Length, 1609.344),
+    unit(
+        &["nmi", "nautical mile", "nautical miles"],
+        Dimension::Length,
+        1852.0,
+    ),
+    // square metres
+    unit(&["mm²", "mm2", "sq mm"], Dimension::Area, 1e-6),
+    unit(&["cm²", "cm2", "sq cm"], Dimension::Area, 1e-4),
+    unit(&["m²", "m2", "sq m"], Dimension::Area, 1.0),
+    unit(&["km²", "km2", "sq km"], Dimension::Area, 1e6),
+    unit(&["in²", "in2", "sq in"], Dimension::Area, 0.00064516),
+    unit(
+        &["ft²", "ft2", "sq ft", "sqft"],
+        Dimension::Area,
+        0.09290304,
+    ),
+    unit(&["yd²", "yd2", "sq yd"], Dimension::Area, 0.83612736),
+    unit(&["mi²", "mi2", "sq mi"], Dimension::Area, 2589988.110336),
+    unit(&["ha", "hectare", "hectares"], Dimension::Area, 1e4),
+    unit(&["acre", "acres"], Dimension::Area, 4046.8564224),
+    // litres, the imperial-looking ones are US customary
+    unit(
+        &[
+            "ml",
+            "millilitre",
+            "millilitres",
+            "milliliter",
+            "milliliters",
+        ],
+        Dimension::Volume,
+        1e-3,
+    ),
+    unit(
+        &[
+            "cl",
+            "centilitre",
+            "centilitres",
+            "centiliter",
+            "centiliters",
+        ],
+        Dimension::Volume,
+        1e-2,
+    ),
+    unit(
+        &["dl", "decilitre", "decilitres", "deciliter", "deciliters"],
+        Dimension::Volume,
+        1e-1,
+    ),
+    unit(
+        &["l", "litre", "litres", "liter", "liters"],
+        Dimension::Volume,
+        1.0,
+    ),
+    unit(
+        &[
+            "m³",
+            "m3",
+            "cubic metre",
+            "cubic metres",
+            "cubic meter",
+            "cubic meters",
+        ],
+        Dimension::Volume,
+        1e3,
+    ),
+    unit(
+        &["tsp", "teaspoon", "teaspoons"],
+        Dimension::Volume,
+        0.00492892159375,
+    ),
+    unit(
+        &["tbsp", "tablespoon", "tablespoons"],
+        Dimension::Volume,
+        0.01478676478125,
+    ),
+    unit(
+        &["fl oz", "floz", "fluid ounce", "fluid ounces"],
+        Dimension::Volume,
+        0.0295735295625,
+    ),
+    unit(&["cup", "cups"], Dimension::Volume, 0.2365882365),
+    unit(&["pt", "pint", "pints"], Dimension::Volume, 0.473176473),
+    unit(&["qt", "quart", "quarts"], Dimension::Volume, 0.946352946),
+    unit(
+        &["gal", "gallon", "gallons"],
+        Dimension::Volume,
+        3.785411784,
+    ),
+    // kilograms
+    unit(&["mg", "milligram", "milligrams"], Dimension::Mass, 1e-6),
+    unit(&["g", "gram", "grams"], Dimension::Mass, 1e-3),
+    unit(
+        &["kg", "kilogram", "kilograms", "kilo", "kilos"],
+        Dimension::Mass,
+        1.0,
+    ),
+    unit(&["t", "tonne", "tonnes"], Dimension::Mass, 1e3),
+    unit(&["oz", "ounce", "ounces"], Dimension::Mass, 0.028349523125),
+    unit(
+        &["lb", "lbs", "pound", "pounds"],
+        Dimension::Mass,
+        0.45359237,
+    ),
+    unit(&["st", "stone", "stones"], Dimension::Mass, 6.35029318),
+    // seconds
+    unit(&["ns", "nanosecond", "nanoseconds"], Dimension::Time, 1e-9),
+    unit(
+        &["µs", "us", "microsecond", "microseconds"],
+        Dimension::Time,
+        1e-6,
+    ),
+    unit(
+        &["ms", "millisecond", "milliseconds"],
+        Dimension::Time,
+        1e-3,
+    ),
+    unit(
+        &["s", "sec", "secs", "second", "seconds"],
+        Dimension::Time,
+        1.0,
+    ),
+    unit(&["min", "mins", "minute", "minutes"], Dimension::Time, 60.0),
+    unit(
+        &["h", "hr", "hrs", "hour", "hours"],
+        Dimension::Time,
+        3600.0,
+    ),
+    unit(&["d", "day", "days"], Dimension::Time, 86400.0),
+    unit(&["wk", "week", "weeks"], Dimension::Time, 604800.0),
+    // a Julian year, 365.25 days
+    unit(&["yr", "year", "years"], Dimension::Time, 31557600.0),
+    // metres per second
+    unit(&["m/s", "mps"], Dimension::Speed, 1.0),
+    unit(&["km/h", "kph", "kmh"], Dimension::Speed, 1.0 / 3.6),
+    unit(&["mph", "mi/h"], Dimension::Speed, 0.44704),
+    unit(&["ft/s", "fps"], Dimension::Speed, 0.3048),
+    unit(
+        &["kn", "kt", "knot", "knots"],
+        Dimension::Speed,
+        1852.0 / 3600.0,
+    ),
+    // kelvin
+    Unit {
+        names: &["°C", "c", "celsius", "degc"],
+        dimension: Dimension::Temperature,
+        factor: 1.0,
+        offset: 273.15,
+    },
+    Unit {
+        names: &["°F", "f", "fahrenheit", "degf"],
+        dimension: Dimension::Temperature,
+        factor: 5.0 / 9.0,
+        offset: 273.15 - 32.0 * 5.0 / 9.0,
+    },
+    unit(&["K", "kelvin"], Dimension::Temperature, 1.0),
+    // bytes, KB and up are powers of 1000 and KiB and up powers of 1024
+    unit(&["bit", "bits"], Dimension::Data, 0.125),
+    unit(
+        &["kbit", "kbits", "kilobit", "kilobits"],
+        Dimension::Data,
+        125.0,
+    ),
+    unit(
+        &["Mbit", "mbits", "megabit", "megabits"],
+        Dimension::Data,
+        125e3,
+    ),
+    unit(
+        &["Gbit", "gbits", "gigabit", "gigabits"],
+        Dimension::Data,
+        125e6,
+    ),
+    unit(&["B", "byte", "bytes"], Dimension::Data, 1.0),
+    unit(&["KB", "kilobyte", "kilobytes"], Dimension::Data, 1e3),
+    unit(&["MB", "megabyte", "megabytes"], Dimension::Data, 1e6),
+    unit(&["GB", "gigabyte", "gigabytes"], Dimension::Data, 1e9),
+    unit(&["TB", "terabyte", "terabytes"], Dimension::Data, 1e12),
+    unit(&["PB", "petabyte", "petabytes"], Dimension::Data, 1e15),
+    unit(&["KiB", "kibibyte", "kibibytes"], Dimension::Data, 1024.0),
+    unit(
+        &["MiB", "mebibyte", "mebibytes"],
+        Dimension::Data,
+        1048576.0,
+    ),
+    unit(
+        &["GiB", "gibibyte", "gibibytes"],
+        Dimension::Data,
+        1073741824.0,
+    ),
+    unit(
+        &["TiB", "tebibyte", "tebibytes"],
+        Dimension::Data,
+        1099511627776.0,
+    ),
+    unit(
+        &["PiB", "pebibyte", "pebibytes"],
+        Dimension::Data,
+        1125899906842624.0,
+    ),
+];
+
+// what goes between the amount and the unit it's wanted in
+const CONNECTORS: [&str; 6] = [" to ", " in ", " as ", " into ", "->", "→"];
+
+pub struct Conversion {
+    pub amount: f64,
+    pub from: &'static str,
+    pub value: f64,
+    pub to: &'static str,
+}
+
+// Reads "<amount> <unit> to <unit>", where the amount can be a sum like 2*1.5. "in" being both a
+// unit and a connector is why every connector in the query gets tried.
+pub fn convert(query: &str) -> Option<Conversion> {
+    CONNECTORS.iter().find_map(|connector| {
+        // not match_indices, the spaces around " in " in "3 in in mm" overlap
+        query.char_indices().find_map(|(idx, _)| {
+            if !query[idx..].starts_with(connector) {
+                return None;
+            }
+            let from_part = &query[..idx];
+            let to = find_unit(&query[idx + connector.len()..])?;
+            let (amount, from) = split_amount(from_part)?;
+            if from.dimension != to.dimension {
+                return None;
+            }
+            let base = amount * from.factor + from.offset;
+            Some(Conversion {
+                amount,
+                from: from.names[0],
+                value: (base - to.offset) / to.factor,
+                to: to.names[0],
+            })
+        })
+    })
+}
+
+fn find_unit(name: &str) -> Option<&'static Unit> {
+    let name = name.trim().to_lowercase();
+    // "°c" or "deg c" as well as "c"
+    let name = name.strip_prefix("deg ").unwrap_or(&name);
+    UNITS
+        .iter()
+        .find(|unit| unit.names.iter().any(|n| n.to_lowercase() == name))
+}
+
+// The amount and unit of something like "12 in" or "12in", taking the longest unit that fits
+fn split_amount(text: &str) -> Option<(f64, &'static Unit)> {
+    let text = text.trim();
+    text.char_indices().skip(1).find_map(|(idx, _)| {
+        let unit = find_unit(&text[idx..])?;
+        let calculation = evaluate(&text[..idx])?;
+        Some((calculation.value, unit))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_converts(query: &str, value: f64, from: &str, to: &str) {
+        let conversion = convert(query).unwrap_or_else(|| panic!("{} didn't convert", query));
+        assert!(
+            (conversion.value - value).abs() < 1e-9 * value.abs().max(1.0),
+            "{} gave {}, expected {}",
+            query,
+            conversion.value,
+            value
+        );
+        assert_eq!(conversion.from, from);
+        assert_eq!(conversion.to, to);
+    }
+
+    #[test]
+    fn converts_the_examples() {
+        assert_converts("12 in to cm", 30.48, "in", "cm");
+        assert_converts("70 F in C", 21.0 + 1.0 / 9.0, "°F", "°C");
+        assert_converts("3 GiB to MB", 3221.225472, "GiB", "MB");
+        assert_converts("90 min in h", 1.5, "min", "h");
+    }
+
+    #[test]
+    fn in_is_a_unit_and_a_connector() {
+        assert_converts("3 in in mm", 76.2, "in", "mm");
+        assert_converts("12in to cm", 30.48, "in", "cm");
+        assert_converts("1 m in in", 1.0 / 0.0254, "m", "in");
+    }
+
+    #[test]
+    fn temperatures_use_their_offsets() {
+        assert_converts("100 °C to F", 212.0, "°C", "°F");
+        assert_converts("0 K to C", -273.15, "K", "°C");
+    }
+
+    #[test]
+    fn amounts_can_be_sums() {
+        assert_converts("2*1.5 kg to g", 3000.0, "kg", "g");
+    }
+
+    #[test]
+    fn mismatched_dimensions_dont_convert() {
+        assert!(convert("5 m to kg").is_none());
+        assert!(convert("3 GiB to s").is_none());
+        assert!(convert("70 F in cm").is_none());
+    }
+
+    #[test]
+    fn other_queries_dont_convert() {
+        assert!(convert("firefox").is_none());
+        assert!(convert("firefox in chrome").is_none());
+        assert!(convert("12 in").is_none());
+        assert!(convert("in to cm").is_none());
+    }
+}